@@ -1,5 +1,20 @@
 extern crate napi_build;
 
+use std::{env, fs};
+
 fn main() {
-  napi_build::setup();
+  if env::var_os("CARGO_FEATURE_NODE").is_some() {
+    napi_build::setup();
+  }
+
+  // Expose the resolved swc version for cache keys and `version()`.
+  println!("cargo:rerun-if-changed=Cargo.lock");
+  let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+  let swc_version = lock
+    .split("[[package]]")
+    .find(|package| package.contains("\nname = \"swc\"\n"))
+    .and_then(|package| package.lines().find(|line| line.starts_with("version = ")))
+    .map(|line| line.trim_start_matches("version = ").trim_matches('"').to_string())
+    .unwrap_or_else(|| "unknown".to_string());
+  println!("cargo:rustc-env=SWC_VERSION={}", swc_version);
 }
@@ -0,0 +1,121 @@
+// Shared by the `require` hook (register.cjs), the ESM loader (loader.mjs)
+// and the Jest transformer (jest.cjs).
+const {loadBinding} = require('@node-rs/helper')
+const path = require('path')
+
+const bindings = loadBinding(
+  path.join(__dirname, './native'),
+  'swc-wallaby',
+  '@live/swc-wallaby'
+)
+
+/** Extensions compiled by default. */
+const EXTENSIONS = ['.ts', '.tsx', '.jsx', '.mts', '.cts']
+
+/**
+ * Options of the hooks, which are not passed to the addon:
+ *
+ * - `extensions`: compiled extensions, default `EXTENSIONS`.
+ * - `ignore`: a function of the filename or an array of regexes, default
+ *   `[/node_modules/]`.
+ *
+ * Everything else is passed to `transformSync()`, and `.swcrc` files are
+ * read unless `swcrc` is `false`. `SWC_WALLABY_OPTIONS` may hold defaults as
+ * JSON.
+ */
+function resolveOptions(options) {
+  const env = process.env.SWC_WALLABY_OPTIONS
+  const {
+    extensions = EXTENSIONS,
+    ignore = [/node_modules/],
+    ...transformOptions
+  } = {...(env ? JSON.parse(env) : {}), ...options}
+
+  const isIgnored =
+    typeof ignore === 'function'
+      ? ignore
+      : (filename) => ignore.some((re) => new RegExp(re).test(filename))
+
+  return {extensions, isIgnored, transformOptions}
+}
+
+/**
+ * Options for compiling `filename`. `moduleType` is the module format Node
+ * will load, `commonjs` or `es6`, unless the options set one. The parser
+ * follows the extension unless the options set one.
+ */
+function buildOptions(filename, transformOptions, moduleType) {
+  const options = {
+    ...transformOptions,
+    filename,
+    module: transformOptions.module ?? {type: moduleType},
+  }
+
+  const ext = path.extname(filename)
+  if (!options.jsc?.parser) {
+    const isTs = ['.ts', '.tsx', '.mts', '.cts'].includes(ext)
+    const jsx = ext === '.tsx' || ext === '.jsx'
+    options.jsc = {
+      ...options.jsc,
+      parser: isTs
+        ? {syntax: 'typescript', tsx: jsx}
+        : {syntax: 'ecmascript', jsx},
+    }
+  }
+
+  return options
+}
+
+/**
+ * Compiles `src` with an inline source map, see `buildOptions`. A file which
+ * `.swcrc` ignores is returned unchanged if it is plain JavaScript, and is an
+ * error otherwise, as Node could not load it.
+ */
+function compile(src, filename, transformOptions, moduleType) {
+  const options = {
+    ...buildOptions(filename, transformOptions, moduleType),
+    sourceMaps: 'inline',
+  }
+
+  const result = bindings.transformSync(src, false, toBuffer(options))
+  if (result.ignored && !['.js', '.mjs', '.cjs'].includes(path.extname(filename))) {
+    throw new Error(
+      `${filename} is ignored by .swcrc, so it cannot be compiled. ` +
+        'Add it to the `ignore` option of the hook instead, or change the ' +
+        '`test` and `exclude` of the .swcrc.'
+    )
+  }
+  return result.code
+}
+
+function toBuffer(options) {
+  return Buffer.from(JSON.stringify(options))
+}
+
+/**
+ * Makes `Error.prepareStackTrace` map stack traces through the inline source
+ * maps. Uses Node's own support where available (Node >= 14.18 or 16.6), or
+ * `source-map-support` if it is installed.
+ */
+function enableSourceMaps() {
+  if (typeof process.setSourceMapsEnabled === 'function') {
+    process.setSourceMapsEnabled(true)
+    return
+  }
+
+  try {
+    require('source-map-support').install({environment: 'node'})
+  } catch (e) {
+    // Stack traces point into the compiled code.
+  }
+}
+
+module.exports = {
+  bindings,
+  buildOptions,
+  compile,
+  enableSourceMaps,
+  resolveOptions,
+  toBuffer,
+  EXTENSIONS,
+}
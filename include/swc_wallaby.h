@@ -0,0 +1,48 @@
+#ifndef SWC_WALLABY_H
+#define SWC_WALLABY_H
+
+/* Generated by cbindgen from src/capi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Version of the C ABI. It changes when the signatures of the exported
+// functions or the request or response shapes change incompatibly.
+#define SWC_WALLABY_ABI_VERSION 1
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns [SWC_WALLABY_ABI_VERSION], so hosts can check that they load a
+// compatible library.
+unsigned int swc_wallaby_abi_version(void);
+
+// Compiles the JSON `request` and returns the JSON response. Never returns
+// null.
+//
+// # Safety
+//
+// `request` must be null or a NUL-terminated string, which is only read
+// during the call.
+char *swc_wallaby_transform(const char *request);
+
+// Returns the versions of this library, swc and the passes as JSON, like
+// `version()` in JS. Never returns null.
+char *swc_wallaby_version(void);
+
+// Releases a string returned by this library. Null is ignored.
+//
+// # Safety
+//
+// `s` must be null or a string returned by this library which was not
+// released yet.
+void swc_wallaby_free_string(char *s);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SWC_WALLABY_H */
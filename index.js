@@ -19,33 +19,64 @@ const bindings = loadBinding(
   '@live/swc-wallaby'
 )
 
-export async function transform(src, options) {
+/**
+ * `options.signal` is an `AbortSignal`. Aborting it, or exceeding
+ * `options.timeoutMs`, rejects with an error whose `code` is `'Cancelled'`.
+ * With compiled options, the signal is passed as `{signal}` in place of a
+ * third argument.
+ */
+export async function transform(src, options, {signal} = {}) {
   const isModule = typeof src !== 'string'
+
+  if (options instanceof CompiledOptions) {
+    return withSignal(signal, (handle) =>
+      bindings.transform(
+        isModule ? JSON.stringify(src) : src,
+        isModule,
+        toNativeOptions(options),
+        handle
+      )
+    )
+  }
+
   options = options || {}
 
   if (options?.jsc?.parser) {
     options.jsc.parser.syntax = options.jsc.parser.syntax ?? 'ecmascript'
   }
 
-  const {plugin, ...newOptions} = options
+  const {plugin, signal: optionsSignal, ...newOptions} = options
+  signal = signal ?? optionsSignal
 
   if (plugin) {
     const m =
       typeof src === 'string'
         ? await this.parse(src, options?.jsc?.parser)
         : src
-    return this.transform(plugin(m), newOptions)
+    return this.transform(plugin(m), newOptions, {signal})
   }
 
-  return bindings.transform(
-    isModule ? JSON.stringify(src) : src,
-    isModule,
-    toBuffer(newOptions)
+  return withSignal(signal, (handle) =>
+    bindings.transform(
+      isModule ? JSON.stringify(src) : src,
+      isModule,
+      toBuffer(newOptions),
+      handle
+    )
   )
 }
 
 export function transformSync(src, options) {
   const isModule = typeof src !== 'string'
+
+  if (options instanceof CompiledOptions) {
+    return bindings.transformSync(
+      isModule ? JSON.stringify(src) : src,
+      isModule,
+      toNativeOptions(options)
+    )
+  }
+
   options = options || {}
 
   if (options?.jsc?.parser) {
@@ -67,6 +98,203 @@ export function transformSync(src, options) {
   )
 }
 
+export async function transformFile(path, options, {signal} = {}) {
+  if (!(options instanceof CompiledOptions) && options?.signal) {
+    const {signal: optionsSignal, ...rest} = options
+    signal = signal ?? optionsSignal
+    options = rest
+  }
+
+  return withSignal(signal, (handle) =>
+    bindings.transformFile(path, false, toNativeOptions(options), handle)
+  )
+}
+
+export function transformFileSync(path, options) {
+  return bindings.transformFileSync(path, false, toNativeOptions(options))
+}
+
+/**
+ * Key for external transform caches. It changes whenever the output of
+ * `transform(source, {...options, filename})` could change.
+ */
+export function getCacheKey(source, filename, options) {
+  return bindings.getCacheKey(
+    source,
+    filename ?? '',
+    toNativeOptions(options)
+  )
+}
+
+/**
+ * Versions of the addon, swc and the custom passes.
+ */
+export function version() {
+  return bindings.version()
+}
+
+/**
+ * Source of a mock module with the exports of the ES module `source`, without
+ * running it. Functions and classes become `factory` (`jest.fn()` by
+ * default), and `header` is put before the mocks.
+ */
+export function generateAutomock(source, filename, {factory, header} = {}) {
+  return bindings.generateAutomock(
+    source,
+    filename ?? '',
+    toBuffer({factory, header})
+  )
+}
+
+/**
+ * Totals of the per-phase timings and sizes over all transforms in this
+ * process.
+ */
+export function getStats() {
+  return bindings.getStats()
+}
+
+export function resetStats() {
+  bindings.resetStats()
+}
+
+/**
+ * Enables Chrome trace export of transforms, passes and range collection.
+ * Events are written to `file`, or each event object is passed to
+ * `callback`. `all` also traces swc itself. Can only be called once, and not
+ * at all if `SWC_WALLABY_TRACE` is set.
+ */
+export function initTracing({file, callback, all} = {}) {
+  bindings.initTracing(
+    file ?? null,
+    // Thread-safe functions are called error first.
+    callback ? (err, json) => callback(JSON.parse(json)) : undefined,
+    !!all
+  )
+}
+
+export function flushTracing() {
+  bindings.flushTracing()
+}
+
+/**
+ * Holds default options, e.g. `cacheDir`, which are merged into the options
+ * of every call. The defaults may be compiled options, into which only
+ * `filename` and `signal` can be merged.
+ */
+export class Compiler {
+  constructor(options) {
+    this.options = options || {}
+  }
+
+  transform(src, options) {
+    const [merged, signal] = this.merge(options)
+    return transform(src, merged, {signal})
+  }
+
+  transformSync(src, options) {
+    return transformSync(src, this.merge(options)[0])
+  }
+
+  transformFile(path, options) {
+    const [merged, signal] = this.merge(options)
+    return transformFile(path, merged, {signal})
+  }
+
+  transformFileSync(path, options) {
+    return transformFileSync(path, this.merge(options)[0])
+  }
+
+  /**
+   * Returns the options of a call and its signal.
+   */
+  merge(options) {
+    if (options instanceof CompiledOptions) {
+      return [options, undefined]
+    }
+    if (!(this.options instanceof CompiledOptions)) {
+      return [{...this.options, ...options}, undefined]
+    }
+
+    const {filename, signal, ...rest} = options || {}
+    if (Object.keys(rest).length > 0) {
+      throw new TypeError(
+        `Cannot merge ${Object.keys(rest).join(', ')} into compiled options`
+      )
+    }
+    const merged =
+      filename === undefined
+        ? this.options
+        : this.options.withFilename(filename)
+    return [merged, signal]
+  }
+}
+
+/**
+ * Options deserialized once on the native side. Pass these in place of an
+ * options object to skip serialization on every call.
+ */
+class CompiledOptions {
+  constructor(handle, filename) {
+    this.handle = handle
+    this.filename = filename
+  }
+
+  /**
+   * The same options for another file, without deserializing them again.
+   */
+  withFilename(filename) {
+    return new CompiledOptions(this.handle, filename)
+  }
+}
+
+export function compileOptions(options) {
+  if (options instanceof CompiledOptions) {
+    return options
+  }
+
+  return new CompiledOptions(bindings.compileOptions(toNativeOptions(options)))
+}
+
+function toNativeOptions(options) {
+  if (options instanceof CompiledOptions) {
+    return options.filename === undefined
+      ? options.handle
+      : {handle: options.handle, filename: options.filename}
+  }
+
+  options = options || {}
+
+  if (options?.jsc?.parser) {
+    options.jsc.parser.syntax = options.jsc.parser.syntax ?? 'ecmascript'
+  }
+
+  return toBuffer(options)
+}
+
+/**
+ * Calls `run` with a native abort handle which is triggered by `signal`.
+ */
+async function withSignal(signal, run) {
+  if (!signal) {
+    return run(null)
+  }
+
+  const handle = bindings.createAbortHandle()
+  const onAbort = () => bindings.abort(handle)
+  if (signal.aborted) {
+    onAbort()
+  } else {
+    signal.addEventListener('abort', onAbort, {once: true})
+  }
+
+  try {
+    return await run(handle)
+  } finally {
+    signal.removeEventListener('abort', onAbort)
+  }
+}
+
 function toBuffer(t) {
   return Buffer.from(JSON.stringify(t))
 }
@@ -0,0 +1,69 @@
+// Jest transformer:
+//
+//     transform: {
+//       '^.+\\.[jt]sx?$': ['@live/swc-wallaby/jest.cjs', {jsc: {...}}],
+//     }
+//
+// The options are those of `transform()`, and `.swcrc` files are read unless
+// `swcrc` is `false`. Modules are compiled to ES modules if Jest supports
+// them for the file (`supportsStaticESM`), and to CommonJS otherwise, unless
+// `module` is set. With coverage, the counters are added by the addon on the
+// ranges it collects (`instrument`), instead of by babel-plugin-istanbul.
+// `jest.mock` calls are hoisted (`hoistMocks`) unless disabled.
+const crypto = require('crypto')
+const {bindings, buildOptions, toBuffer} = require('./hook.cjs')
+
+function createTransformer(userOptions = {}) {
+  const optionsFor = (filename, transformOptions = {}) => ({
+    ...buildOptions(
+      filename,
+      {hoistMocks: true, ...userOptions},
+      transformOptions.supportsStaticESM ? 'es6' : 'commonjs'
+    ),
+    sourceMaps: true,
+    instrument: !!transformOptions.instrument,
+  })
+
+  return {
+    canInstrument: true,
+
+    process(src, filename, ...rest) {
+      const options = optionsFor(filename, transformOptionsOf(rest))
+      return toResult(bindings.transformSync(src, false, toBuffer(options)))
+    },
+
+    async processAsync(src, filename, ...rest) {
+      const options = optionsFor(filename, transformOptionsOf(rest))
+      return toResult(await bindings.transform(src, false, toBuffer(options), null))
+    },
+
+    getCacheKey(src, filename, ...rest) {
+      const transformOptions = transformOptionsOf(rest)
+      const options = optionsFor(filename, transformOptions)
+      const key = bindings.getCacheKey(src, filename, toBuffer(options))
+      const configString =
+        rest.length > 1 ? rest[0] : transformOptions.configString
+
+      return crypto
+        .createHash('sha1')
+        .update(key)
+        .update('\0')
+        .update(configString ?? '')
+        .digest('hex')
+    },
+  }
+}
+
+/**
+ * Jest 27 passes `(src, filename, transformOptions)`, and Jest 26
+ * `(src, filename, config, transformOptions)`.
+ */
+function transformOptionsOf(rest) {
+  return rest.length > 1 ? rest[1] ?? {} : rest[0] ?? {}
+}
+
+function toResult(output) {
+  return {code: output.code, map: output.map}
+}
+
+module.exports = {...createTransformer(), createTransformer}
@@ -0,0 +1,60 @@
+// ESM loader, for Node >= 16.12:
+//
+//     node --loader @live/swc-wallaby/loader.mjs test.ts
+//
+// Options are read from `SWC_WALLABY_OPTIONS` (see hook.cjs). Files are
+// compiled to ES modules, and `./a.js` resolves to `./a.ts` if only that
+// exists, as in TypeScript. CommonJS files, including `.cts`, are compiled
+// by the `require` hook, which this installs too.
+import fs from 'fs'
+import path from 'path'
+import {fileURLToPath} from 'url'
+import hook from './hook.cjs'
+import './register.cjs'
+
+const {compile, enableSourceMaps, resolveOptions} = hook
+const {extensions, isIgnored, transformOptions} = resolveOptions()
+
+enableSourceMaps()
+
+export async function resolve(specifier, context, defaultResolve) {
+  try {
+    return await defaultResolve(specifier, context, defaultResolve)
+  } catch (err) {
+    if (err.code !== 'ERR_MODULE_NOT_FOUND' || !/\.[cm]?js$/.test(specifier)) {
+      throw err
+    }
+
+    for (const ext of extensions) {
+      const candidate = specifier.replace(/\.[cm]?js$/, ext)
+      try {
+        return await defaultResolve(candidate, context, defaultResolve)
+      } catch (e) {
+        // Try the next extension.
+      }
+    }
+    throw err
+  }
+}
+
+export async function load(url, context, defaultLoad) {
+  if (!url.startsWith('file:')) {
+    return defaultLoad(url, context, defaultLoad)
+  }
+
+  const filename = fileURLToPath(url)
+  if (!extensions.includes(path.extname(filename)) || isIgnored(filename)) {
+    return defaultLoad(url, context, defaultLoad)
+  }
+
+  if (path.extname(filename) === '.cts') {
+    return {format: 'commonjs', shortCircuit: true}
+  }
+
+  const src = await fs.promises.readFile(filename, 'utf8')
+  return {
+    format: 'module',
+    source: compile(src, filename, transformOptions, 'es6'),
+    shortCircuit: true,
+  }
+}
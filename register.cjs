@@ -0,0 +1,54 @@
+// CommonJS `require` hook:
+//
+//     node -r @live/swc-wallaby/register.cjs test.ts
+//
+// Requiring this file registers the hook with the options in
+// `SWC_WALLABY_OPTIONS`. Call `register(options)` to replace it with other
+// options (see hook.cjs), and the returned function to remove it.
+const Module = require('module')
+const fs = require('fs')
+const {compile, enableSourceMaps, resolveOptions} = require('./hook.cjs')
+
+let unregister = null
+
+function register(options) {
+  if (unregister) {
+    unregister()
+  }
+
+  const {extensions, isIgnored, transformOptions} = resolveOptions(options)
+  const previous = new Map()
+
+  for (const ext of extensions) {
+    const original = Module._extensions[ext] ?? Module._extensions['.js']
+    previous.set(ext, Module._extensions[ext])
+
+    Module._extensions[ext] = function (module, filename) {
+      if (isIgnored(filename)) {
+        return original(module, filename)
+      }
+
+      const src = fs.readFileSync(filename, 'utf8')
+      module._compile(compile(src, filename, transformOptions, 'commonjs'), filename)
+    }
+  }
+
+  enableSourceMaps()
+
+  unregister = () => {
+    for (const [ext, handler] of previous) {
+      if (handler) {
+        Module._extensions[ext] = handler
+      } else {
+        delete Module._extensions[ext]
+      }
+    }
+    unregister = null
+  }
+
+  return unregister
+}
+
+register()
+
+module.exports = {register}
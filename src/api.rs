@@ -0,0 +1,345 @@
+//! Rust API of the compiler, which does not depend on N-API.
+//!
+//! This is what the JS bindings do, with plain Rust types:
+//!
+//! ```ignore
+//! use swc_wallaby::api::{options_from_json, transform, TransformRequest};
+//!
+//! let options = options_from_json(r#"{"filename": "a.js"}"#)?;
+//! let result = transform(TransformRequest::code("a?.b", options))?;
+//! for range in result.ranges {
+//!     println!("{}:{}", range.start_line, range.start_column);
+//! }
+//! ```
+//!
+//! Build the crate with `default-features = false` to leave out the N-API
+//! bindings.
+
+use crate::{
+    cache::options_digest,
+    cancel::CancelToken,
+    compiler,
+    panic::catch_panic,
+    ranges::Ranges,
+    stack::run_with_stack,
+    transform::{load_file, process_file},
+};
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    error, fmt, mem,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use swc_common::{
+    errors::{DiagnosticBuilder, Emitter, Handler, Level, HANDLER},
+    FileName, SourceMap, Span,
+};
+
+pub use crate::{
+    coverage::FileCoverage, dependencies::Dependency, generated::SkipReason,
+    source_map::SourceMapOutput, stats::TransformStats, swcrc::SwcrcReport,
+    test_discovery::TestTree, transform::TransformOptions,
+};
+
+/// Parses options in the JSON format of the JS API.
+///
+/// Unlike `TransformOptions::default()`, this applies the defaults of the JS
+/// API, e.g. reading `.swcrc` files.
+pub fn options_from_json(json: &str) -> Result<TransformOptions, Error> {
+    let mut options: TransformOptions = serde_json::from_str(json)?;
+    options.options_digest = Some(options_digest(json.as_bytes()));
+
+    Ok(options)
+}
+
+/// What to compile.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Code(String),
+    /// Read from disk. It is also the default `filename`.
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransformRequest {
+    pub source: Source,
+    pub options: TransformOptions,
+}
+
+impl TransformRequest {
+    pub fn code(code: impl Into<String>, options: TransformOptions) -> Self {
+        TransformRequest {
+            source: Source::Code(code.into()),
+            options,
+        }
+    }
+
+    pub fn file(path: impl Into<PathBuf>, options: TransformOptions) -> Self {
+        TransformRequest {
+            source: Source::File(path.into()),
+            options,
+        }
+    }
+}
+
+/// A range of the input. Lines are one-based and columns zero-based, as
+/// Wallaby expects them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Range {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Range {
+    fn from_span(cm: &SourceMap, span: Span) -> Self {
+        let (lo, hi) = (cm.lookup_char_pos(span.lo()), cm.lookup_char_pos(span.hi()));
+        Range {
+            start_line: lo.line,
+            start_column: lo.col.0,
+            end_line: hi.line,
+            end_column: hi.col.0,
+        }
+    }
+
+    /// Converts the `[startLine, startColumn, endLine, endColumn]` arrays of
+    /// the JS output.
+    fn from_ranges(ranges: Ranges) -> Vec<Self> {
+        ranges
+            .into_iter()
+            .filter_map(|r| match r[..] {
+                [start_line, start_column, end_line, end_column] => Some(Range {
+                    start_line,
+                    start_column,
+                    end_line,
+                    end_column,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+/// An error or warning reported by swc or a pass.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub filename: Option<String>,
+    pub range: Option<Range>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformResult {
+    pub code: String,
+    pub map: Option<SourceMapOutput>,
+    pub ranges: Vec<Range>,
+    /// The file is ignored by `.swcrc`, and `code` is the original source.
+    pub ignored: bool,
+    /// The file looks generated, and was compiled without ranges.
+    pub skipped: Option<SkipReason>,
+    pub swcrc: Option<SwcrcReport>,
+    /// Set with `instrument`.
+    pub coverage: Option<FileCoverage>,
+    /// Set with `tests`.
+    pub tests: Option<TestTree>,
+    /// Set with `dependencies`.
+    pub dependencies: Option<Vec<Dependency>>,
+    pub stats: Option<TransformStats>,
+    /// Warnings. Errors fail the transform.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A failed transform.
+///
+/// `error` can be downcast to [Cancelled](crate::cancel::Cancelled),
+/// [Panicked](crate::panic::Panicked) or
+/// [TooDeeplyNested](crate::stack::TooDeeplyNested).
+#[derive(Debug)]
+pub struct TransformError {
+    pub error: Error,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        for d in &self.diagnostics {
+            write!(f, "\n{:?}: {}", d.severity, d.message)?;
+            if let (Some(filename), Some(range)) = (&d.filename, &d.range) {
+                write!(f, " ({}:{}:{})", filename, range.start_line, range.start_column + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for TransformError {}
+
+/// Compiles the request with the compiler shared by the process, on a thread
+/// with the configured stack size.
+pub fn transform(request: TransformRequest) -> Result<TransformResult, TransformError> {
+    let c = compiler();
+    let TransformRequest { source, mut options } = request;
+    if let Source::File(ref path) = source {
+        options.set_default_filename(path);
+    }
+
+    let cancel = CancelToken::new(None, options.timeout_ms);
+    let diagnostics = Arc::new(Mutex::new(vec![]));
+    let collector = Collector {
+        cm: c.cm.clone(),
+        diagnostics: diagnostics.clone(),
+    };
+
+    let result = run_with_stack(options.stack_size(), || {
+        catch_panic(&options.swc.filename, || {
+            let handler = Handler::with_emitter(true, false, Box::new(collector));
+            HANDLER.set(&handler, || {
+                c.run(|| {
+                    let fm = match source {
+                        Source::Code(code) => {
+                            let name = if options.swc.filename.is_empty() {
+                                FileName::Anon
+                            } else {
+                                FileName::Real(options.swc.filename.clone().into())
+                            };
+                            c.cm.new_source_file(name, code)
+                        }
+                        Source::File(ref path) => load_file(&c, path)?,
+                    };
+                    let output = process_file(&c, fm, &handler, &options, &cancel)?;
+                    if handler.has_errors() {
+                        bail!("failed to compile {}", options.swc.filename)
+                    }
+                    Ok(output)
+                })
+            })
+        })
+    });
+
+    let diagnostics = mem::take(&mut *diagnostics.lock().unwrap_or_else(|e| e.into_inner()));
+    match result {
+        Ok(output) => Ok(TransformResult {
+            code: output.code,
+            map: output.map,
+            ranges: Range::from_ranges(output.ranges),
+            ignored: output.ignored,
+            skipped: output.skipped,
+            swcrc: output.swcrc,
+            coverage: output.coverage,
+            tests: output.tests,
+            dependencies: output.dependencies,
+            stats: output.stats,
+            diagnostics,
+        }),
+        Err(error) => Err(TransformError { error, diagnostics }),
+    }
+}
+
+/// Collects the diagnostics emitted through a [Handler].
+struct Collector {
+    cm: Arc<SourceMap>,
+    diagnostics: Arc<Mutex<Vec<Diagnostic>>>,
+}
+
+impl Emitter for Collector {
+    fn emit(&mut self, db: &DiagnosticBuilder<'_>) {
+        let severity = match db.level {
+            Level::Bug | Level::Fatal | Level::PhaseFatal | Level::Error => Severity::Error,
+            Level::Warning => Severity::Warning,
+            Level::Note | Level::FailureNote => Severity::Note,
+            Level::Help => Severity::Help,
+            Level::Cancelled => return,
+        };
+        let span = db.span.primary_span().filter(|span| !span.is_dummy());
+
+        self.diagnostics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Diagnostic {
+                severity,
+                message: db.message(),
+                filename: span.map(|span| self.cm.span_to_filename(span).to_string()),
+                range: span.map(|span| Range::from_span(&self.cm, span)),
+            });
+    }
+}
+
+#[test]
+fn test_from_ranges() {
+    assert_eq!(
+        Range::from_ranges(vec![vec![1, 0, 1, 4], vec![]]),
+        vec![Range {
+            start_line: 1,
+            start_column: 0,
+            end_line: 1,
+            end_column: 4,
+        }]
+    );
+}
+
+#[test]
+fn test_transform() {
+    let options = |json: &str| options_from_json(json).unwrap();
+
+    let result = transform(TransformRequest::code(
+        "const a = 1;\nfoo(a);\n",
+        options(r#"{"filename": "a.js", "swcrc": false}"#),
+    ))
+    .unwrap();
+    assert!(result.code.contains("foo(a)"));
+    assert!(result.ranges.contains(&Range {
+        start_line: 2,
+        start_column: 0,
+        end_line: 2,
+        end_column: 6,
+    }));
+    assert!(!result.ignored && result.diagnostics.is_empty());
+
+    // Errors of the parser and of the passes come with their range.
+    let err = transform(TransformRequest::code(
+        "let a = ;\n",
+        options(r#"{"filename": "a.js", "swcrc": false}"#),
+    ))
+    .unwrap_err();
+    let diagnostic = &err.diagnostics[0];
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.filename.as_deref(), Some("a.js"));
+    assert_eq!(diagnostic.range.map(|r| r.start_line), Some(1));
+
+    let err = transform(TransformRequest::code(
+        "import {a} from './a';\njest.mock('./b', () => ({b: a}));\n",
+        options(r#"{"filename": "a.test.js", "swcrc": false, "hoistMocks": true}"#),
+    ))
+    .unwrap_err();
+    assert_eq!(err.diagnostics.len(), 1);
+    assert!(err.diagnostics[0].message.contains("Invalid variable access: a"));
+    assert!(err.to_string().contains("(a.test.js:2:29)"));
+
+    // Files are read from disk and named by their path.
+    let dir = std::env::temp_dir().join(format!("swc-wallaby-api-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("b.js");
+    std::fs::write(&path, "export const b = 1;\n").unwrap();
+    let result =
+        transform(TransformRequest::file(&path, options(r#"{"swcrc": false}"#))).unwrap();
+    assert!(result.code.contains("b = 1"));
+    assert!(transform(TransformRequest::file(dir.join("missing.js"), options("{}"))).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
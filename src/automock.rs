@@ -0,0 +1,894 @@
+//! `generateAutomock()`: a mock module derived statically from the exports of
+//! an ES module, in place of Jest's automock, which requires the real module.
+//!
+//! - Functions, including arrow functions and function expressions assigned
+//!   to constants, become the factory, `jest.fn()` by default.
+//! - Classes become the factory, with the factory on their methods on the
+//!   prototype and static methods on the class.
+//! - Literals are copied, object literals are mocked property by property,
+//!   arrays are empty and other values are `undefined`.
+//! - Enums are copied where their values are constant, with the reverse
+//!   mapping of numeric members, and namespaces are mocked by their exported
+//!   functions, classes, enums and namespaces.
+//! - Names which refer to other top-level declarations share their mock, so
+//!   `export {f as g}` and `export const g = f` export the mock of `f`.
+//!   Imports they refer to are imported by the mock too.
+//! - Destructured exports, like `export const {a} = o`, are `undefined`.
+//! - Re-exports are kept, so they resolve to the mock of the other module if
+//!   it has one. Exported imports are turned into re-exports.
+//!
+//! Type-only exports and ambient declarations are left out. CommonJS exports
+//! are not analyzed.
+
+#[cfg(feature = "node")]
+use crate::{
+    get_compiler,
+    panic::catch_panic,
+    stack::{run_with_stack, DEFAULT_STACK_SIZE},
+    util::MapErr,
+};
+use anyhow::Error;
+#[cfg(feature = "node")]
+use napi::{CallContext, JsBuffer, JsString};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    path::Path,
+    sync::Arc,
+};
+use swc::{try_with_handler, Compiler};
+use swc_common::{FileName, SourceMap, Spanned, DUMMY_SP};
+use swc_ecmascript::{
+    ast::*,
+    parser::{EsConfig, Syntax, TsConfig},
+    visit::{Node, Visit, VisitWith},
+};
+
+/// Name of the local holding the default export.
+const DEFAULT_LOCAL: &str = "__automock_default";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutomockOptions {
+    /// Expression creating a mock function, e.g. `vi.fn()`.
+    #[serde(default = "default_factory")]
+    pub factory: String,
+    /// Code put before the mocks, e.g. `import {vi} from 'vitest'`.
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+impl Default for AutomockOptions {
+    fn default() -> Self {
+        AutomockOptions {
+            factory: default_factory(),
+            header: None,
+        }
+    }
+}
+
+fn default_factory() -> String {
+    "jest.fn()".into()
+}
+
+/// The mock of one exported value.
+#[derive(Debug, Clone, PartialEq)]
+enum Mock {
+    Function,
+    Class {
+        methods: Vec<String>,
+        statics: Vec<String>,
+    },
+    /// A JS expression.
+    Value(String),
+}
+
+fn undefined() -> Mock {
+    Mock::Value("undefined".into())
+}
+
+/// Generates the mock module of `src`.
+pub fn generate_automock(
+    c: &Arc<Compiler>,
+    src: String,
+    filename: &str,
+    options: &AutomockOptions,
+) -> Result<String, Error> {
+    let module = try_with_handler(c.cm.clone(), |handler| {
+        let name = if filename.is_empty() {
+            FileName::Anon
+        } else {
+            FileName::Real(filename.into())
+        };
+        let fm = c.cm.new_source_file(name, src);
+        c.parse_js(fm, handler, Default::default(), syntax_for(filename), true, false)
+    })?;
+
+    let module = match module {
+        Program::Module(module) => module,
+        Program::Script(..) => unreachable!("parsed as a module"),
+    };
+
+    Ok(Automock::new(&c.cm, &module, options).emit(&module))
+}
+
+/// The parser syntax for the extension of `filename`.
+fn syntax_for(filename: &str) -> Syntax {
+    let ext = Path::new(filename).extension().and_then(|ext| ext.to_str());
+    match ext {
+        Some("ts") | Some("mts") | Some("cts") | Some("tsx") => Syntax::Typescript(TsConfig {
+            tsx: ext == Some("tsx"),
+            decorators: true,
+            dynamic_import: true,
+            ..Default::default()
+        }),
+        _ => Syntax::Es(EsConfig {
+            jsx: true,
+            decorators: true,
+            dynamic_import: true,
+            export_default_from: true,
+            ..Default::default()
+        }),
+    }
+}
+
+/// A top-level binding of the module.
+#[derive(Debug, Clone)]
+enum Binding {
+    Function,
+    Class(Class),
+    /// A variable with its initializer. Destructured variables have none.
+    Var(Option<Box<Expr>>),
+    Enum(TsEnumDecl),
+    Namespace(TsModuleDecl),
+    /// An import of the module and name, `None` for namespace imports.
+    Import(String, Option<String>),
+    /// A type or an ambient declaration, which has no value.
+    Type,
+}
+
+/// Collects the top-level bindings in source order, without entering
+/// functions and blocks.
+#[derive(Debug, Default)]
+struct Bindings(Vec<(String, Binding)>);
+
+impl Bindings {
+    fn add(&mut self, name: &Ident, binding: Binding) {
+        self.0.push((name.sym.to_string(), binding));
+    }
+}
+
+impl Visit for Bindings {
+    fn visit_import_decl(&mut self, n: &ImportDecl, _parent: &dyn Node) {
+        let src = n.src.value.to_string();
+        for specifier in &n.specifiers {
+            let (local, imported) = match specifier {
+                ImportSpecifier::Named(s) => (
+                    &s.local,
+                    Some(s.imported.as_ref().unwrap_or(&s.local).sym.to_string()),
+                ),
+                ImportSpecifier::Default(s) => (&s.local, Some("default".to_string())),
+                ImportSpecifier::Namespace(s) => (&s.local, None),
+            };
+            let binding = if n.type_only {
+                Binding::Type
+            } else {
+                Binding::Import(src.clone(), imported)
+            };
+            self.add(local, binding);
+        }
+    }
+
+    fn visit_fn_decl(&mut self, n: &FnDecl, _parent: &dyn Node) {
+        self.add(&n.ident, if n.declare { Binding::Type } else { Binding::Function });
+    }
+
+    fn visit_class_decl(&mut self, n: &ClassDecl, _parent: &dyn Node) {
+        let binding = if n.declare {
+            Binding::Type
+        } else {
+            Binding::Class(n.class.clone())
+        };
+        self.add(&n.ident, binding);
+    }
+
+    fn visit_var_decl(&mut self, n: &VarDecl, _parent: &dyn Node) {
+        for decl in &n.decls {
+            match &decl.name {
+                Pat::Ident(name) if n.declare => self.add(&name.id, Binding::Type),
+                Pat::Ident(name) => self.add(&name.id, Binding::Var(decl.init.clone())),
+                pat => {
+                    let mut names = PatNames(vec![]);
+                    pat.visit_with(decl, &mut names);
+                    for name in names.0 {
+                        let binding = if n.declare { Binding::Type } else { Binding::Var(None) };
+                        self.add(&name, binding);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_ts_enum_decl(&mut self, n: &TsEnumDecl, _parent: &dyn Node) {
+        let binding = if n.declare {
+            Binding::Type
+        } else {
+            Binding::Enum(n.clone())
+        };
+        self.add(&n.id, binding);
+    }
+
+    fn visit_ts_module_decl(&mut self, n: &TsModuleDecl, _parent: &dyn Node) {
+        // `declare module 'x'` declares a module, not a binding.
+        if let TsModuleName::Ident(ref id) = n.id {
+            let binding = if n.declare {
+                Binding::Type
+            } else {
+                Binding::Namespace(n.clone())
+            };
+            self.add(id, binding);
+        }
+    }
+
+    fn visit_ts_interface_decl(&mut self, n: &TsInterfaceDecl, _parent: &dyn Node) {
+        self.add(&n.id, Binding::Type);
+    }
+
+    fn visit_ts_type_alias_decl(&mut self, n: &TsTypeAliasDecl, _parent: &dyn Node) {
+        self.add(&n.id, Binding::Type);
+    }
+
+    fn visit_export_default_decl(&mut self, n: &ExportDefaultDecl, _parent: &dyn Node) {
+        match &n.decl {
+            DefaultDecl::Fn(FnExpr {
+                ident: Some(ident), ..
+            }) => self.add(ident, Binding::Function),
+            DefaultDecl::Class(ClassExpr {
+                ident: Some(ident),
+                class,
+            }) => self.add(ident, Binding::Class(class.clone())),
+            _ => {}
+        }
+    }
+
+    fn visit_block_stmt(&mut self, _: &BlockStmt, _parent: &dyn Node) {}
+
+    fn visit_function(&mut self, _: &Function, _parent: &dyn Node) {}
+
+    fn visit_expr(&mut self, _: &Expr, _parent: &dyn Node) {}
+}
+
+/// Collects the names bound by a pattern, without its default values.
+struct PatNames(Vec<Ident>);
+
+impl Visit for PatNames {
+    fn visit_binding_ident(&mut self, n: &BindingIdent, _parent: &dyn Node) {
+        self.0.push(n.id.clone());
+    }
+
+    fn visit_assign_pat_prop(&mut self, n: &AssignPatProp, _parent: &dyn Node) {
+        self.0.push(n.key.clone());
+    }
+
+    fn visit_expr(&mut self, _: &Expr, _parent: &dyn Node) {}
+}
+
+/// Emits the mocks of the exports. Exported locals are declared once, and
+/// the locals and imports their values refer to are declared or imported
+/// before them, so aliases share their mock.
+struct Automock<'a> {
+    cm: &'a SourceMap,
+    options: &'a AutomockOptions,
+    bindings: HashMap<String, Binding>,
+    /// Locals declared or imported in the output.
+    declared: HashSet<String>,
+    /// Locals whose mock is being generated, to break cycles.
+    declaring: HashSet<String>,
+    out: String,
+}
+
+impl<'a> Automock<'a> {
+    fn new(cm: &'a SourceMap, module: &Module, options: &'a AutomockOptions) -> Self {
+        let mut bindings = Bindings::default();
+        module.visit_with(&Invalid { span: DUMMY_SP }, &mut bindings);
+
+        Automock {
+            cm,
+            options,
+            bindings: bindings.0.into_iter().collect(),
+            declared: Default::default(),
+            declaring: Default::default(),
+            out: String::new(),
+        }
+    }
+
+    fn emit(mut self, module: &Module) -> String {
+        if let Some(ref header) = self.options.header {
+            self.out.push_str(header.trim_end());
+            self.out.push('\n');
+        }
+
+        module.visit_with(&Invalid { span: DUMMY_SP }, &mut self);
+        self.out
+    }
+
+    fn mock(&mut self, expr: &Expr) -> Mock {
+        match expr {
+            Expr::Fn(..) | Expr::Arrow(..) => Mock::Function,
+            Expr::Class(c) => class(&c.class),
+            Expr::Ident(ident) => Mock::Value(self.reference(&ident.sym)),
+            Expr::Paren(ParenExpr { expr, .. })
+            | Expr::TsAs(TsAsExpr { expr, .. })
+            | Expr::TsConstAssertion(TsConstAssertion { expr, .. })
+            | Expr::TsTypeAssertion(TsTypeAssertion { expr, .. })
+            | Expr::TsNonNull(TsNonNullExpr { expr, .. }) => self.mock(expr),
+            Expr::Lit(Lit::JSXText(..)) => undefined(),
+            Expr::Lit(..) => Mock::Value(self.snippet(expr)),
+            Expr::Tpl(tpl) if tpl.exprs.is_empty() => Mock::Value(self.snippet(expr)),
+            Expr::Unary(UnaryExpr { arg, .. }) if matches!(**arg, Expr::Lit(Lit::Num(..))) => {
+                Mock::Value(self.snippet(expr))
+            }
+            Expr::Array(..) => Mock::Value("[]".into()),
+            Expr::Object(obj) => Mock::Value(self.object(obj)),
+            _ => undefined(),
+        }
+    }
+
+    /// Mocks the properties of an object literal which have a static key.
+    fn object(&mut self, obj: &ObjectLit) -> String {
+        let mut props = vec![];
+        for prop in &obj.props {
+            let prop = match prop {
+                PropOrSpread::Prop(prop) => prop,
+                PropOrSpread::Spread(..) => continue,
+            };
+            let (key, mock) = match &**prop {
+                Prop::KeyValue(KeyValueProp { key, value }) => (key, self.mock(value)),
+                Prop::Method(MethodProp { key, .. }) => (key, Mock::Function),
+                Prop::Shorthand(ident) => {
+                    let value = self.reference(&ident.sym);
+                    props.push(format!("{}: {}", ident.sym, value));
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(key) = prop_name(key) {
+                props.push(format!("{}: {}", key, self.value(&mock)));
+            }
+        }
+
+        if props.is_empty() {
+            "{}".into()
+        } else {
+            format!("{{ {} }}", props.join(", "))
+        }
+    }
+
+    /// The values of an enum, counting like TypeScript. Numeric members also
+    /// get their reverse mapping, e.g. `E[0] === "A"`.
+    fn enum_(&self, e: &TsEnumDecl) -> String {
+        let mut members = vec![];
+        let mut values = HashMap::new();
+        let mut next = Some(0.0);
+        for member in &e.members {
+            let key = match &member.id {
+                TsEnumMemberId::Ident(ident) => ident.sym.to_string(),
+                TsEnumMemberId::Str(s) => s.value.to_string(),
+            };
+            let value = match member.init.as_deref() {
+                None => next,
+                Some(init @ Expr::Lit(Lit::Str(..))) => {
+                    members.push(format!("{}: {}", quote(&key), self.snippet(init)));
+                    next = None;
+                    continue;
+                }
+                Some(init) => enum_value(init, &e.id.sym, &values),
+            };
+            next = value.map(|n| n + 1.0);
+
+            match value {
+                Some(n) => {
+                    members.push(format!("{}: {}", quote(&key), number(n)));
+                    members.push(format!("{}: {}", quote(&number(n)), quote(&key)));
+                    values.insert(key, n);
+                }
+                None => members.push(format!("{}: undefined", quote(&key))),
+            }
+        }
+
+        format!("{{ {} }}", members.join(", "))
+    }
+
+    /// The exported functions, classes, enums and namespaces of a namespace.
+    /// Its variables are `undefined`.
+    fn namespace(&self, body: Option<&TsNamespaceBody>) -> String {
+        let mut props = vec![];
+        match body {
+            Some(TsNamespaceBody::TsModuleBlock(block)) => {
+                let mut bindings = Bindings::default();
+                for item in &block.body {
+                    if let ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) = item {
+                        export.decl.visit_with(export, &mut bindings);
+                    }
+                }
+                for (name, binding) in bindings.0 {
+                    let value = match binding {
+                        Binding::Function | Binding::Class(..) => self.options.factory.clone(),
+                        Binding::Enum(e) => self.enum_(&e),
+                        Binding::Namespace(ns) => self.namespace(ns.body.as_ref()),
+                        Binding::Var(..) | Binding::Import(..) => "undefined".into(),
+                        Binding::Type => continue,
+                    };
+                    props.push(format!("{}: {}", name, value));
+                }
+            }
+            // The inner names of `namespace A.B {}`.
+            Some(TsNamespaceBody::TsNamespaceDecl(decl)) => {
+                props.push(format!("{}: {}", decl.id.sym, self.namespace(Some(&decl.body))))
+            }
+            None => {}
+        }
+
+        if props.is_empty() {
+            "{}".into()
+        } else {
+            format!("{{ {} }}", props.join(", "))
+        }
+    }
+
+    fn snippet(&self, expr: &Expr) -> String {
+        self.cm
+            .span_to_snippet(expr.span())
+            .unwrap_or_else(|_| "undefined".into())
+    }
+
+    /// The mock of the top-level binding `name`.
+    fn local(&mut self, name: &str) -> Mock {
+        self.declaring.insert(name.to_string());
+        let mock = match self.bindings.get(name).cloned() {
+            Some(Binding::Function) => Mock::Function,
+            Some(Binding::Class(c)) => class(&c),
+            Some(Binding::Var(Some(init))) => self.mock(&init),
+            Some(Binding::Enum(e)) => Mock::Value(self.enum_(&e)),
+            Some(Binding::Namespace(ns)) => Mock::Value(self.namespace(ns.body.as_ref())),
+            Some(Binding::Var(None)) | Some(Binding::Import(..)) | Some(Binding::Type) | None => {
+                undefined()
+            }
+        };
+        self.declaring.remove(name);
+        mock
+    }
+
+    /// An expression for the top-level binding `name`, which is declared or
+    /// imported first. Globals and types are `undefined`.
+    fn reference(&mut self, name: &str) -> String {
+        if self.declared.contains(name) {
+            return name.into();
+        }
+
+        match self.bindings.get(name).cloned() {
+            Some(Binding::Import(src, imported)) => {
+                let line = match imported.as_deref() {
+                    Some("default") => format!("import {} from {};", name, quote(&src)),
+                    Some(imported) if imported == name => {
+                        format!("import {{ {} }} from {};", name, quote(&src))
+                    }
+                    Some(imported) => {
+                        format!("import {{ {} as {} }} from {};", imported, name, quote(&src))
+                    }
+                    None => format!("import * as {} from {};", name, quote(&src)),
+                };
+                self.line(line);
+                self.declared.insert(name.into());
+                name.into()
+            }
+            Some(Binding::Type) | None => "undefined".into(),
+            // A cycle, e.g. `const a = {b}, b = {a}`.
+            Some(_) if self.declaring.contains(name) => "undefined".into(),
+            Some(_) => {
+                let mock = self.local(name);
+                self.declare(name, &mock, false);
+                name.into()
+            }
+        }
+    }
+
+    /// A mock as a single expression, for object properties.
+    fn value(&self, mock: &Mock) -> String {
+        match mock {
+            Mock::Function | Mock::Class { .. } => self.options.factory.clone(),
+            Mock::Value(value) => value.clone(),
+        }
+    }
+
+    /// Exports the top-level binding `name` as `exported`.
+    fn export_local(&mut self, name: &str, exported: &str) {
+        match self.bindings.get(name).cloned() {
+            Some(Binding::Import(src, imported)) => {
+                self.reexport(&src, imported.as_deref(), exported)
+            }
+            Some(Binding::Type) => {}
+            Some(_) if name == exported && !self.declared.contains(name) => {
+                let mock = self.local(name);
+                self.declare(name, &mock, true);
+            }
+            Some(_) => {
+                let local = self.reference(name);
+                self.line(match exported {
+                    "default" => format!("export default {};", local),
+                    _ if local == exported => format!("export {{ {} }};", local),
+                    _ => format!("export {{ {} as {} }};", local, exported),
+                });
+            }
+            // A global, e.g. `export {window}`.
+            None => self.export_mock(exported, &undefined()),
+        }
+    }
+
+    /// Declares a new local with `mock` and exports it as `exported`.
+    fn export_mock(&mut self, exported: &str, mock: &Mock) {
+        if exported == "default" {
+            self.declare(DEFAULT_LOCAL, mock, false);
+            self.line(format!("export default {};", DEFAULT_LOCAL));
+        } else {
+            self.declare(exported, mock, true);
+        }
+    }
+
+    /// Declares `local` with `mock`.
+    fn declare(&mut self, local: &str, mock: &Mock, export: bool) {
+        let value = self.value(mock);
+        let keyword = if export { "export const" } else { "const" };
+        self.line(format!("{} {} = {};", keyword, local, value));
+
+        if let Mock::Class { methods, statics } = mock {
+            for method in methods {
+                let factory = self.options.factory.clone();
+                self.line(format!("{}.prototype{} = {};", local, member(method), factory));
+            }
+            for method in statics {
+                let factory = self.options.factory.clone();
+                self.line(format!("{}{} = {};", local, member(method), factory));
+            }
+        }
+
+        self.declared.insert(local.into());
+    }
+
+    fn reexport(&mut self, src: &str, imported: Option<&str>, exported: &str) {
+        let line = match (imported, exported) {
+            (Some(imported), _) => {
+                format!("export {{ {} as {} }} from {};", imported, exported, quote(src))
+            }
+            (None, "default") => format!(
+                "import * as {0} from {1};\nexport default {0};",
+                DEFAULT_LOCAL,
+                quote(src)
+            ),
+            (None, _) => format!("export * as {} from {};", exported, quote(src)),
+        };
+        self.line(line);
+    }
+
+    fn line(&mut self, line: String) {
+        let _ = writeln!(self.out, "{}", line);
+    }
+}
+
+/// Visits the exports, in source order.
+impl Visit for Automock<'_> {
+    fn visit_stmt(&mut self, _: &Stmt, _parent: &dyn Node) {}
+
+    fn visit_export_decl(&mut self, n: &ExportDecl, _parent: &dyn Node) {
+        let mut bindings = Bindings::default();
+        n.decl.visit_with(n, &mut bindings);
+        for (name, _) in bindings.0 {
+            self.export_local(&name, &name);
+        }
+    }
+
+    fn visit_named_export(&mut self, n: &NamedExport, _parent: &dyn Node) {
+        if n.type_only {
+            return;
+        }
+
+        for specifier in &n.specifiers {
+            match (specifier, &n.src) {
+                (ExportSpecifier::Named(s), Some(src)) => {
+                    let exported = s.exported.as_ref().unwrap_or(&s.orig);
+                    self.reexport(&src.value, Some(&*s.orig.sym), &exported.sym);
+                }
+                (ExportSpecifier::Namespace(s), Some(src)) => {
+                    self.reexport(&src.value, None, &s.name.sym)
+                }
+                (ExportSpecifier::Default(s), Some(src)) => {
+                    self.reexport(&src.value, Some("default"), &s.exported.sym)
+                }
+                (ExportSpecifier::Named(s), None) => {
+                    let exported = s.exported.as_ref().unwrap_or(&s.orig);
+                    self.export_local(&s.orig.sym, &exported.sym);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_export_all(&mut self, n: &ExportAll, _parent: &dyn Node) {
+        self.line(format!("export * from {};", quote(&n.src.value)))
+    }
+
+    fn visit_export_default_decl(&mut self, n: &ExportDefaultDecl, _parent: &dyn Node) {
+        match &n.decl {
+            DefaultDecl::Fn(FnExpr {
+                ident: Some(ident), ..
+            })
+            | DefaultDecl::Class(ClassExpr {
+                ident: Some(ident), ..
+            }) => self.export_local(&ident.sym, "default"),
+            DefaultDecl::Fn(..) => self.export_mock("default", &Mock::Function),
+            DefaultDecl::Class(c) => self.export_mock("default", &class(&c.class)),
+            DefaultDecl::TsInterfaceDecl(..) => {}
+        }
+    }
+
+    fn visit_export_default_expr(&mut self, n: &ExportDefaultExpr, _parent: &dyn Node) {
+        match &*n.expr {
+            Expr::Ident(ident) => self.export_local(&ident.sym, "default"),
+            expr => {
+                let mock = self.mock(expr);
+                self.export_mock("default", &mock);
+            }
+        }
+    }
+}
+
+/// The methods and static methods of `class`, without accessors, private
+/// and computed members.
+fn class(class: &Class) -> Mock {
+    let (mut methods, mut statics) = (vec![], vec![]);
+    for member in &class.body {
+        if let ClassMember::Method(m) = member {
+            if m.kind != MethodKind::Method || m.is_abstract {
+                continue;
+            }
+            if let Some(name) = prop_name(&m.key) {
+                if m.is_static {
+                    statics.push(name);
+                } else {
+                    methods.push(name);
+                }
+            }
+        }
+    }
+
+    Mock::Class { methods, statics }
+}
+
+/// A static property name as JS source, an identifier or a string.
+fn prop_name(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(s) => Some(quote(&s.value)),
+        PropName::Num(n) => Some(n.value.to_string()),
+        _ => None,
+    }
+}
+
+/// `.name` for identifiers, `[key]` otherwise.
+fn member(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    {
+        format!(".{}", name)
+    } else {
+        format!("[{}]", name)
+    }
+}
+
+/// The value of a constant enum member, which may use literals, arithmetic
+/// and the earlier `values` of enum `name`.
+fn enum_value(expr: &Expr, name: &str, values: &HashMap<String, f64>) -> Option<f64> {
+    let value = |expr: &Expr| enum_value(expr, name, values);
+    let int = |expr: &Expr| value(expr).map(|n| n as i64 as i32);
+
+    Some(match expr {
+        Expr::Lit(Lit::Num(n)) => n.value,
+        Expr::Paren(ParenExpr { expr, .. }) => value(expr)?,
+        Expr::Ident(ident) => *values.get(&*ident.sym)?,
+        Expr::Member(MemberExpr {
+            obj: ExprOrSuper::Expr(obj),
+            prop,
+            computed: false,
+            ..
+        }) => match (&**obj, &**prop) {
+            (Expr::Ident(obj), Expr::Ident(prop)) if &*obj.sym == name => {
+                *values.get(&*prop.sym)?
+            }
+            _ => return None,
+        },
+        Expr::Unary(UnaryExpr { op, arg, .. }) => match op {
+            UnaryOp::Minus => -value(arg)?,
+            UnaryOp::Plus => value(arg)?,
+            UnaryOp::Tilde => !int(arg)? as f64,
+            _ => return None,
+        },
+        Expr::Bin(BinExpr {
+            op, left, right, ..
+        }) => match op {
+            BinaryOp::Add => value(left)? + value(right)?,
+            BinaryOp::Sub => value(left)? - value(right)?,
+            BinaryOp::Mul => value(left)? * value(right)?,
+            BinaryOp::Div => value(left)? / value(right)?,
+            BinaryOp::Mod => value(left)? % value(right)?,
+            BinaryOp::Exp => value(left)?.powf(value(right)?),
+            BinaryOp::BitOr => (int(left)? | int(right)?) as f64,
+            BinaryOp::BitAnd => (int(left)? & int(right)?) as f64,
+            BinaryOp::BitXor => (int(left)? ^ int(right)?) as f64,
+            BinaryOp::LShift => int(left)?.wrapping_shl(int(right)? as u32) as f64,
+            BinaryOp::RShift => int(left)?.wrapping_shr(int(right)? as u32) as f64,
+            BinaryOp::ZeroFillRShift => {
+                (int(left)? as u32).wrapping_shr(int(right)? as u32) as f64
+            }
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// A number as JavaScript prints it.
+fn number(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".into()
+    } else if n == f64::INFINITY {
+        "Infinity".into()
+    } else if n == f64::NEG_INFINITY {
+        "-Infinity".into()
+    } else {
+        n.to_string()
+    }
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+#[cfg(feature = "node")]
+#[js_function(3)]
+pub fn generate_automock_sync(cx: CallContext) -> napi::Result<JsString> {
+    let c = get_compiler(&cx);
+
+    let src = cx.get::<JsString>(0)?.into_utf8()?.as_str()?.to_owned();
+    let filename = cx.get::<JsString>(1)?.into_utf8()?.as_str()?.to_owned();
+    let options: AutomockOptions = serde_json::from_slice(&cx.get::<JsBuffer>(2)?.into_value()?)
+        .map_err(Error::from)
+        .convert_err()?;
+
+    let code = run_with_stack(DEFAULT_STACK_SIZE, || {
+        catch_panic(&filename, || generate_automock(&c, src, &filename, &options))
+    })
+    .convert_err()?;
+
+    cx.env.create_string(&code)
+}
+
+#[test]
+fn test_generate_automock() {
+    let src = r#"
+        import dep, {helper} from './dep';
+        export function f() {}
+        export const g = () => 1, n = 42, s = 'a', o = {a: 1, b() {}, c: []};
+        export class C {
+            m() {}
+            static s() {}
+            get x() { return 1; }
+        }
+        export {helper, g as h};
+        export * from './other';
+        export {x as y} from './other';
+        export default class {}
+    "#;
+    let code = generate_automock(
+        &crate::compiler(),
+        src.into(),
+        "a.js",
+        &AutomockOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        code,
+        "export const f = jest.fn();
+export const g = jest.fn();
+export const n = 42;
+export const s = 'a';
+export const o = { a: 1, b: jest.fn(), c: [] };
+export const C = jest.fn();
+C.prototype.m = jest.fn();
+C.s = jest.fn();
+export { helper as helper } from \"./dep\";
+export { g as h };
+export * from \"./other\";
+export { x as y } from \"./other\";
+const __automock_default = jest.fn();
+export default __automock_default;
+"
+    );
+}
+
+#[test]
+fn test_generate_automock_typescript() {
+    let src = r#"
+        import dep, * as ns from './dep';
+        import {helper} from './dep';
+        import type {T} from './types';
+        export interface I {}
+        export type U = string;
+        export {T};
+        export declare function ambient(): void;
+        export function f(): void {}
+        export const alias = f, table = {k: f, helper, n: -1};
+        export const {a, b: [c]} = table as any;
+        export enum E { A, B = 'b', C = 5, D, F = -1, G, H = 1 << 3 | G }
+        export namespace N { export function g() {} export const v = 1; }
+        export {ns};
+        export default {dep, x: 1};
+    "#;
+    let code = generate_automock(
+        &crate::compiler(),
+        src.into(),
+        "a.ts",
+        &AutomockOptions {
+            factory: "vi.fn()".into(),
+            header: Some("import {vi} from 'vitest'".into()),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        code,
+        "import {vi} from 'vitest'
+export const f = vi.fn();
+export const alias = f;
+import { helper } from \"./dep\";
+export const table = { k: f, helper: helper, n: -1 };
+export const a = undefined;
+export const c = undefined;
+export const E = { \"A\": 0, \"0\": \"A\", \"B\": 'b', \"C\": 5, \"5\": \"C\", \"D\": 6, \
+\"6\": \"D\", \"F\": -1, \"-1\": \"F\", \"G\": 0, \"0\": \"G\", \"H\": 8, \"8\": \"H\" };
+export const N = { g: vi.fn(), v: undefined };
+export * as ns from \"./dep\";
+import dep from \"./dep\";
+const __automock_default = { dep: dep, x: 1 };
+export default __automock_default;
+"
+    );
+}
+
+#[test]
+fn test_generate_automock_default_exports() {
+    let generate = |src: &str| {
+        generate_automock(&crate::compiler(), src.into(), "a.js", &Default::default()).unwrap()
+    };
+
+    assert_eq!(
+        generate("class A { m() {} }\nexport default A;\nexport {A};"),
+        "const A = jest.fn();\nA.prototype.m = jest.fn();\nexport default A;\nexport { A };\n"
+    );
+    assert_eq!(
+        generate("export default function f() {}\nexport const g = f;"),
+        "const f = jest.fn();\nexport default f;\nexport const g = f;\n"
+    );
+    assert_eq!(
+        generate("import dep from './dep';\nexport default dep;"),
+        "export { default as default } from \"./dep\";\n"
+    );
+    assert_eq!(
+        generate("const a = {b}, b = {a};\nexport default [a];"),
+        "const __automock_default = [];\nexport default __automock_default;\n"
+    );
+    assert_eq!(
+        generate("const a = {b}, b = {a};\nexport {a};"),
+        "const b = { a: undefined };\nexport const a = { b: b };\n"
+    );
+}
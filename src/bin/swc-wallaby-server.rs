@@ -0,0 +1,348 @@
+//! Compile server speaking newline-delimited JSON-RPC 2.0 over stdin and
+//! stdout, for tools which cannot load the N-API addon.
+//!
+//! Every line on stdin is one request, and every response is written as one
+//! line to stdout. Requests are handled in order with one shared compiler.
+//! Options and outputs are the same as those of the JS API:
+//!
+//! - `transform {src | file, options}` returns `{code, map, ranges, ...}`.
+//! - `transformMany {items: [{src | file, options}]}` compiles the items in
+//!   parallel and returns an array of outputs or `{error: {message}}`.
+//! - `minify {src, options}` returns `{code, map}`.
+//! - `parse {src, options, filename}` returns the AST as JSON.
+//! - `getCacheKey {src, filename, options}` returns the key as a string.
+//! - `version` returns the versions of the addon, swc and the passes.
+//! - `shutdown` responds with `null` and exits.
+//!
+//! Failed compilations are errors with code `-32000` whose message contains
+//! the formatted diagnostics.
+
+use anyhow::{bail, Context as _, Error};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use swc::{
+    config::{JsMinifyOptions, ParseOptions},
+    try_with_handler, Compiler,
+};
+use swc_common::FileName;
+use swc_wallaby::{
+    cache::{options_digest, resolved_cache_key, versions},
+    compiler,
+    panic::{self, catch_panic},
+    stack::{run_with_stack, DEFAULT_STACK_SIZE},
+    trace,
+    transform::{compile_file, compile_source, TransformOptions, TransformOutputWithRanges},
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A compilation failed.
+const COMPILE_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+}
+
+/// Error of one method call.
+enum CallError {
+    InvalidParams(Error),
+    MethodNotFound,
+    Failed(Error),
+}
+
+impl CallError {
+    fn into_object(self) -> ErrorObject {
+        match self {
+            CallError::InvalidParams(err) => ErrorObject {
+                code: INVALID_PARAMS,
+                message: format!("{:?}", err),
+            },
+            CallError::MethodNotFound => ErrorObject {
+                code: METHOD_NOT_FOUND,
+                message: "method not found".into(),
+            },
+            CallError::Failed(err) => ErrorObject {
+                code: COMPILE_ERROR,
+                message: format!("{:?}", err),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransformParams {
+    #[serde(default)]
+    src: Option<String>,
+    /// Read from disk instead of `src`.
+    #[serde(default)]
+    file: Option<PathBuf>,
+    #[serde(default = "empty_object")]
+    options: Value,
+}
+
+#[derive(Deserialize)]
+struct TransformManyParams {
+    items: Vec<TransformParams>,
+}
+
+/// An item of the `transformMany` result.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ItemResult {
+    Ok(TransformOutputWithRanges),
+    Err { error: ErrorObject },
+}
+
+#[derive(Deserialize)]
+struct MinifyParams {
+    src: String,
+    #[serde(default = "empty_object")]
+    options: Value,
+}
+
+#[derive(Deserialize)]
+struct ParseParams {
+    src: String,
+    #[serde(default = "empty_object")]
+    options: Value,
+    #[serde(default)]
+    filename: String,
+}
+
+#[derive(Deserialize)]
+struct CacheKeyParams {
+    src: String,
+    #[serde(default)]
+    filename: String,
+    #[serde(default = "empty_object")]
+    options: Value,
+}
+
+fn empty_object() -> Value {
+    json!({})
+}
+
+fn main() {
+    panic::install_hook();
+    if let Err(err) = trace::init_from_env() {
+        eprintln!("swc-wallaby-server: {:?}", err);
+    }
+
+    let c = compiler();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("swc-wallaby-server: failed to read stdin: {}", err);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, shutdown) = handle_line(&c, &line);
+        if let Some(response) = response {
+            let mut out = stdout.lock();
+            // The client is gone if stdout is closed.
+            if writeln!(out, "{}", response).and_then(|_| out.flush()).is_err() {
+                break;
+            }
+        }
+        if shutdown {
+            break;
+        }
+    }
+
+    trace::flush();
+}
+
+/// Returns the response, if any, and whether the server should exit.
+fn handle_line(c: &Arc<Compiler>, line: &str) -> (Option<Value>, bool) {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return (Some(error_response(Value::Null, PARSE_ERROR, err)), false),
+    };
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(err) => return (Some(error_response(Value::Null, INVALID_REQUEST, err)), false),
+    };
+
+    let shutdown = request.method == "shutdown";
+    let result = call(c, &request.method, request.params);
+
+    // Requests without an id are notifications, which get no response.
+    let response = request.id.map(|id| match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(err) => json!({"jsonrpc": "2.0", "id": id, "error": err.into_object()}),
+    });
+
+    (response, shutdown)
+}
+
+fn error_response(id: Value, code: i64, err: impl ToString) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": code, "message": err.to_string()},
+    })
+}
+
+fn call(c: &Arc<Compiler>, method: &str, params: Value) -> Result<Value, CallError> {
+    match method {
+        "transform" => {
+            let params: TransformParams = parse_params(params)?;
+            let output = transform(c, params).map_err(CallError::Failed)?;
+            to_value(output)
+        }
+        "transformMany" => {
+            let params: TransformManyParams = parse_params(params)?;
+            let outputs: Vec<_> = params
+                .items
+                .into_par_iter()
+                .map(|item| match transform(c, item) {
+                    Ok(output) => ItemResult::Ok(output),
+                    Err(err) => ItemResult::Err {
+                        error: CallError::Failed(err).into_object(),
+                    },
+                })
+                .collect();
+            to_value(outputs)
+        }
+        "minify" => {
+            let params: MinifyParams = parse_params(params)?;
+            to_value(minify(c, params).map_err(CallError::Failed)?)
+        }
+        "parse" => {
+            let params: ParseParams = parse_params(params)?;
+            parse(c, params).map_err(CallError::Failed)
+        }
+        "getCacheKey" => {
+            let params: CacheKeyParams = parse_params(params)?;
+            let mut options =
+                transform_options(&params.options).map_err(CallError::InvalidParams)?;
+            if !params.filename.is_empty() {
+                options.swc.filename = params.filename;
+            }
+            let key = catch_panic(&options.swc.filename, || {
+                resolved_cache_key(c, &params.src, &options)
+            })
+            .map_err(CallError::Failed)?;
+            Ok(Value::String(key))
+        }
+        "version" => to_value(versions()),
+        "shutdown" => Ok(Value::Null),
+        _ => Err(CallError::MethodNotFound),
+    }
+}
+
+fn parse_params<T>(params: Value) -> Result<T, CallError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::from_value(params)
+        .context("invalid params")
+        .map_err(CallError::InvalidParams)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, CallError> {
+    serde_json::to_value(value).map_err(|err| CallError::Failed(err.into()))
+}
+
+/// Deserializes transform options like the N-API functions do, including the
+/// digest used for cache keys.
+fn transform_options(options: &Value) -> Result<TransformOptions, Error> {
+    let mut parsed: TransformOptions =
+        serde_json::from_value(options.clone()).context("invalid options")?;
+    parsed.options_digest = Some(options_digest(options.to_string().as_bytes()));
+
+    Ok(parsed)
+}
+
+fn transform(
+    c: &Arc<Compiler>,
+    params: TransformParams,
+) -> Result<TransformOutputWithRanges, Error> {
+    let mut options = transform_options(&params.options)?;
+
+    match (params.src, params.file) {
+        (Some(src), None) => compile_source(c, src, &options),
+        (None, Some(file)) => {
+            options.set_default_filename(&file);
+            compile_file(c, &file, &options)
+        }
+        _ => bail!("exactly one of `src` and `file` is required"),
+    }
+}
+
+fn minify(c: &Arc<Compiler>, params: MinifyParams) -> Result<swc::TransformOutput, Error> {
+    let MinifyParams { src, options } = params;
+    let options: JsMinifyOptions =
+        serde_json::from_value(options).context("invalid minify options")?;
+
+    run_with_stack(DEFAULT_STACK_SIZE, || {
+        catch_panic("", || {
+            try_with_handler(c.cm.clone(), |handler| {
+                let fm = c.cm.new_source_file(FileName::Anon, src);
+                c.minify(fm, handler, &options)
+            })
+        })
+    })
+}
+
+fn parse(c: &Arc<Compiler>, params: ParseParams) -> Result<Value, Error> {
+    let ParseParams {
+        src,
+        options,
+        filename,
+    } = params;
+    let options: ParseOptions =
+        serde_json::from_value(options).context("invalid parse options")?;
+    let name = if filename.is_empty() {
+        FileName::Anon
+    } else {
+        FileName::Real(filename.clone().into())
+    };
+
+    run_with_stack(DEFAULT_STACK_SIZE, || {
+        catch_panic(&filename, || {
+            try_with_handler(c.cm.clone(), |handler| {
+                c.run(|| {
+                    let fm = c.cm.new_source_file(name, src);
+                    let program = c.parse_js(
+                        fm,
+                        handler,
+                        options.target,
+                        options.syntax,
+                        options.is_module,
+                        options.comments,
+                    )?;
+                    Ok(serde_json::to_value(&program)?)
+                })
+            })
+        })
+    })
+}
@@ -0,0 +1,367 @@
+//! Command-line compiler, for inspecting the output of the addon without
+//! Node.
+//!
+//! ```text
+//! swc-wallaby [options] <file | dir | glob>...
+//! ```
+//!
+//! Every input is compiled to `<out-dir>/<path relative to the cwd>`, with
+//! `.ts(x)` and `.jsx` renamed to `.js`. Next to it, `<name>.js.map` is
+//! written if source maps are enabled, and `<name>.ranges.json` holds the
+//! ranges.
+//!
+//! With `--watch`, the inputs are compiled once and then recompiled whenever
+//! they change (see [watch]).
+
+use anyhow::{bail, Context as _, Error};
+use serde_json::Value;
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::Instant,
+};
+use swc::Compiler;
+use swc_wallaby::{
+    api::options_from_json,
+    compiler,
+    source_map::SourceMapOutput,
+    transform::{compile_file, TransformOptions, TransformOutputWithRanges},
+};
+
+mod watch;
+
+const USAGE: &str = "\
+Usage: swc-wallaby [options] <file | dir | glob>...
+
+Options:
+  -d, --out-dir <dir>     Output directory (required)
+  --options <file|json>   Transform options as a JSON file or inline JSON,
+                          the same as for `transform()`
+  --config-file <path>    Use this .swcrc in addition to the nearest one
+  --no-swcrc              Do not read .swcrc files
+  -s, --source-maps       Write source maps
+  -w, --watch             Recompile files when they change (Linux only)
+  -q, --quiet             Only print errors
+  -h, --help              Print this help
+";
+
+/// Extensions of the files compiled when a directory is given.
+const EXTENSIONS: &[&str] = &["js", "jsx", "mjs", "cjs", "ts", "tsx"];
+
+struct Args {
+    out_dir: PathBuf,
+    options: Value,
+    inputs: Vec<String>,
+    quiet: bool,
+    watch: bool,
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    process::exit(match run(&args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            eprintln!("error: {:?}", err);
+            1
+        }
+    });
+}
+
+fn parse_args(mut argv: impl Iterator<Item = String>) -> Result<Args, Error> {
+    let mut out_dir = None;
+    // Deserialized like the options from JS, so `swcrc` defaults to true.
+    let mut options = serde_json::json!({});
+    let mut inputs = vec![];
+    let mut quiet = false;
+    let mut watch = false;
+
+    while let Some(arg) = argv.next() {
+        let mut value = |name: &str| {
+            argv.next()
+                .with_context(|| format!("missing value for {}", name))
+        };
+
+        match &*arg {
+            "-d" | "--out-dir" => out_dir = Some(PathBuf::from(value(&arg)?)),
+            "--options" => {
+                let value = value(&arg)?;
+                let json = if value.trim_start().starts_with('{') {
+                    value
+                } else {
+                    fs::read_to_string(&value)
+                        .with_context(|| format!("failed to read {}", value))?
+                };
+                let parsed: Value =
+                    serde_json::from_str(&json).context("failed to parse --options")?;
+                merge(&mut options, parsed);
+            }
+            "--config-file" => options["configFile"] = value(&arg)?.into(),
+            "--no-swcrc" => options["swcrc"] = false.into(),
+            "-s" | "--source-maps" => options["sourceMaps"] = true.into(),
+            "-q" | "--quiet" => quiet = true,
+            "-w" | "--watch" => watch = true,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => bail!("unknown option {}", arg),
+            _ => inputs.push(arg),
+        }
+    }
+
+    let out_dir = match out_dir {
+        Some(dir) => dir,
+        None => bail!("--out-dir is required"),
+    };
+    if inputs.is_empty() {
+        bail!("no input files")
+    }
+
+    Ok(Args {
+        out_dir,
+        options,
+        inputs,
+        quiet,
+        watch,
+    })
+}
+
+/// Overwrites the keys of `base` with those of `value`.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Object(base), Value::Object(value)) => {
+            for (k, v) in value {
+                merge(base.entry(k).or_insert(Value::Null), v);
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// State shared by all compilations of one run.
+struct Build<'a> {
+    args: &'a Args,
+    options: TransformOptions,
+    cwd: PathBuf,
+    c: Arc<Compiler>,
+}
+
+impl Build<'_> {
+    /// Compiles `file` and writes its output. Errors are printed, and
+    /// reported as `false`.
+    fn compile(&self, file: &Path) -> bool {
+        let start = Instant::now();
+        let mut options = self.options.clone();
+        options.set_default_filename(file);
+
+        let result = compile_file(&self.c, file, &options)
+            .and_then(|output| write_output(&self.args.out_dir, &self.cwd, file, output));
+        match result {
+            Ok(out) => {
+                if !self.args.quiet {
+                    println!(
+                        "{} -> {} ({:.1}ms)",
+                        file.display(),
+                        out.display(),
+                        start.elapsed().as_secs_f64() * 1000.0
+                    );
+                }
+                true
+            }
+            Err(err) => {
+                eprintln!("error: failed to compile {}\n{:?}\n", file.display(), err);
+                false
+            }
+        }
+    }
+}
+
+/// Compiles all inputs. Returns `false` if any file failed.
+///
+/// In watch mode, this only returns on errors of the watcher.
+fn run(args: &Args) -> Result<bool, Error> {
+    let build = Build {
+        args,
+        // With the digest, so that `cacheDir` works.
+        options: options_from_json(&args.options.to_string())
+            .context("invalid transform options")?,
+        cwd: env::current_dir()?,
+        c: compiler(),
+    };
+
+    let files = expand_inputs(&args.inputs, &args.out_dir)?;
+    if files.is_empty() && !args.watch {
+        bail!("no files matched {}", args.inputs.join(" "))
+    }
+
+    let mut ok = true;
+    for file in files {
+        ok &= build.compile(&file);
+    }
+
+    if args.watch {
+        watch::watch(&build)?;
+    }
+
+    Ok(ok)
+}
+
+/// Resolves files, directories and globs to a sorted list of files, without
+/// those in `out_dir`.
+fn expand_inputs(inputs: &[String], out_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    // Earlier outputs must not be compiled again, e.g. with `-d out .`.
+    let out_dir = fs::canonicalize(out_dir).ok();
+    let is_output = |path: &Path| match out_dir {
+        Some(ref out_dir) => fs::canonicalize(path).map_or(false, |p| p.starts_with(out_dir)),
+        None => false,
+    };
+    let mut files = vec![];
+
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let walk = walkdir::WalkDir::new(path).into_iter();
+            let walked = |entry: &walkdir::DirEntry| {
+                // An input directory is walked even if its name is skipped.
+                let skipped = entry.depth() > 0
+                    && entry.file_type().is_dir()
+                    && is_skipped_dir(entry.file_name());
+                !skipped && !is_output(entry.path())
+            };
+            for entry in walk.filter_entry(walked) {
+                let entry = entry?;
+                if entry.file_type().is_file() && is_source(entry.path()) {
+                    files.push(entry.into_path());
+                }
+            }
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else if input.contains(|c| matches!(c, '*' | '?' | '[')) {
+            for entry in glob::glob(input).with_context(|| format!("invalid glob {}", input))? {
+                let path = entry?;
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        } else {
+            bail!("{} does not exist", input)
+        }
+    }
+
+    files.retain(|file| !is_output(file));
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// `node_modules` and hidden directories like `.git` are not walked for
+/// inputs, nor watched.
+fn is_skipped_dir(name: &OsStr) -> bool {
+    name == "node_modules" || name.to_str().map_or(false, |name| name.starts_with('.'))
+}
+
+fn is_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| EXTENSIONS.contains(&ext))
+}
+
+/// Where the output of `file` is written.
+fn output_path(out_dir: &Path, cwd: &Path, file: &Path) -> PathBuf {
+    let file = file.strip_prefix("./").unwrap_or(file);
+    let rel = match file.strip_prefix(cwd) {
+        Ok(rel) => rel,
+        Err(_) if file.is_relative() => file,
+        // Outside of the cwd, keep only the file name.
+        Err(_) => Path::new(file.file_name().unwrap_or_default()),
+    };
+
+    let out = out_dir.join(rel);
+    match out.extension().and_then(|ext| ext.to_str()) {
+        Some("ts") | Some("tsx") | Some("jsx") => out.with_extension("js"),
+        _ => out,
+    }
+}
+
+/// Writes the code, map and ranges of `file`, and returns the path of the
+/// code.
+fn write_output(
+    out_dir: &Path,
+    cwd: &Path,
+    file: &Path,
+    output: TransformOutputWithRanges,
+) -> Result<PathBuf, Error> {
+    let out = output_path(out_dir, cwd, file);
+    if let Some(dir) = out.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let file_name = out.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut code = output.code;
+    if let Some(map) = output.map {
+        let map_path = out.with_file_name(format!("{}.map", file_name));
+        let json = match map {
+            SourceMapOutput::Json(json) => json,
+            SourceMapOutput::Object(map) => serde_json::to_string(&map)?,
+        };
+        fs::write(&map_path, json)
+            .with_context(|| format!("failed to write {}", map_path.display()))?;
+        code.push_str(&format!("\n//# sourceMappingURL={}.map\n", file_name));
+    }
+
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let ranges_path = out.with_file_name(format!("{}.ranges.json", stem));
+    fs::write(&ranges_path, serde_json::to_string(&output.ranges)?)
+        .with_context(|| format!("failed to write {}", ranges_path.display()))?;
+
+    fs::write(&out, code).with_context(|| format!("failed to write {}", out.display()))?;
+
+    Ok(out)
+}
+
+#[test]
+fn test_output_path() {
+    let (out, cwd) = (Path::new("out"), Path::new("/p"));
+
+    assert_eq!(output_path(out, cwd, Path::new("src/a.ts")), Path::new("out/src/a.js"));
+    assert_eq!(output_path(out, cwd, Path::new("/p/src/a.mjs")), Path::new("out/src/a.mjs"));
+    assert_eq!(output_path(out, cwd, Path::new("/q/b.jsx")), Path::new("out/b.js"));
+}
+
+#[test]
+fn test_expand_inputs_skipped_dirs() {
+    let dir = env::temp_dir().join(format!("swc-wallaby-inputs-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for file in &[
+        "src/a.ts",
+        "out/src/a.js",
+        "out/out/src/a.js",
+        "node_modules/b/index.js",
+        ".git/c.js",
+    ] {
+        let file = dir.join(file);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, "a;").unwrap();
+    }
+
+    let out_dir = dir.join("out");
+    let inputs = [dir.to_string_lossy().into_owned()];
+    assert_eq!(expand_inputs(&inputs, &out_dir).unwrap(), vec![dir.join("src/a.ts")]);
+
+    let glob = [format!("{}/out/**/*.js", dir.display())];
+    assert!(expand_inputs(&glob, &out_dir).unwrap().is_empty());
+
+    let _ = fs::remove_dir_all(&dir);
+}
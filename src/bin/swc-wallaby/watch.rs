@@ -0,0 +1,300 @@
+//! `--watch`: recompiles inputs when they change.
+//!
+//! Directories are watched with inotify: recursively for directory and glob
+//! inputs, and the parent directory for file inputs. Like for the inputs,
+//! `node_modules` and hidden directories are skipped, which also keeps the
+//! number of watches below `fs.inotify.max_user_watches`. Events are collected
+//! until no more arrive for [DEBOUNCE_MS], and every changed file which
+//! matches an input is recompiled once.
+
+use super::Build;
+#[cfg(target_os = "linux")]
+use super::{is_skipped_dir, is_source};
+use anyhow::Error;
+#[cfg(not(target_os = "linux"))]
+use anyhow::bail;
+#[cfg(target_os = "linux")]
+use {
+    anyhow::Context as _,
+    std::{
+        collections::{BTreeSet, HashMap},
+        ffi::{CString, OsStr},
+        fs, io, mem,
+        os::unix::{ffi::OsStrExt, io::RawFd},
+        path::{Component, Path, PathBuf},
+        ptr,
+    },
+};
+
+/// Editors often write a file in several steps, so events are batched.
+#[cfg(target_os = "linux")]
+const DEBOUNCE_MS: i32 = 50;
+
+#[cfg(target_os = "linux")]
+const MASK: u32 = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch(_build: &Build) -> Result<(), Error> {
+    bail!("--watch is only supported on Linux")
+}
+
+/// Watches the inputs of `build` and recompiles changed files. Only returns
+/// on errors.
+#[cfg(target_os = "linux")]
+pub fn watch(build: &Build) -> Result<(), Error> {
+    let inputs = build
+        .args
+        .inputs
+        .iter()
+        .map(|input| WatchedInput::new(input))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Output written into a watched directory must not trigger a rebuild.
+    fs::create_dir_all(&build.args.out_dir)?;
+    let out_dir = fs::canonicalize(&build.args.out_dir)?;
+
+    let mut inotify = Inotify::new(out_dir.clone())?;
+    for input in &inputs {
+        match input {
+            WatchedInput::Dir(dir) => inotify.add(dir, true)?,
+            WatchedInput::File(file) => {
+                inotify.add(file.parent().unwrap_or_else(|| Path::new("")), false)?
+            }
+            WatchedInput::Glob(_, base) => inotify.add(base, true)?,
+        }
+    }
+    if !build.args.quiet {
+        println!("Watching for changes...");
+    }
+
+    loop {
+        let mut changed = BTreeSet::new();
+        inotify.wait(-1, &mut changed)?;
+        while inotify.wait(DEBOUNCE_MS, &mut changed)? {}
+
+        for path in changed {
+            let is_output = fs::canonicalize(&path).map_or(true, |p| p.starts_with(&out_dir));
+            if !is_output && path.is_file() && inputs.iter().any(|input| input.matches(&path)) {
+                build.compile(&path);
+            }
+        }
+    }
+}
+
+/// An input given on the command line.
+#[cfg(target_os = "linux")]
+enum WatchedInput {
+    Dir(PathBuf),
+    File(PathBuf),
+    /// The pattern, and the directory before its first wildcard.
+    Glob(glob::Pattern, PathBuf),
+}
+
+#[cfg(target_os = "linux")]
+impl WatchedInput {
+    fn new(input: &str) -> Result<Self, Error> {
+        let input = input.strip_prefix("./").unwrap_or(input);
+        let path = normalize(Path::new(input));
+        // `.` normalizes to an empty path, which [Inotify::add] watches as
+        // the current directory.
+        if path.as_os_str().is_empty() || path.is_dir() {
+            return Ok(WatchedInput::Dir(path));
+        }
+        if !input.contains(|c| matches!(c, '*' | '?' | '[')) {
+            return Ok(WatchedInput::File(path));
+        }
+
+        let pattern =
+            glob::Pattern::new(input).with_context(|| format!("invalid glob {}", input))?;
+        let base = path
+            .components()
+            .take_while(|c| {
+                !c.as_os_str()
+                    .to_string_lossy()
+                    .contains(|c| matches!(c, '*' | '?' | '['))
+            })
+            .collect();
+
+        Ok(WatchedInput::Glob(pattern, base))
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match self {
+            WatchedInput::Dir(dir) => path.starts_with(dir) && is_source(path),
+            WatchedInput::File(file) => path == file,
+            WatchedInput::Glob(pattern, _) => pattern.matches_path(path),
+        }
+    }
+}
+
+/// Strips `./` components, so paths compare equal to those built from
+/// watched directories.
+#[cfg(target_os = "linux")]
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+struct Inotify {
+    fd: RawFd,
+    /// Watched directories by watch descriptor, and whether new
+    /// subdirectories are watched too.
+    dirs: HashMap<i32, (PathBuf, bool)>,
+    /// Not watched, see [watch].
+    out_dir: PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl Inotify {
+    fn new(out_dir: PathBuf) -> Result<Self, Error> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("failed to initialize inotify");
+        }
+
+        Ok(Inotify {
+            fd,
+            dirs: Default::default(),
+            out_dir,
+        })
+    }
+
+    /// Watches `dir`, and with `recursive` all of its subdirectories.
+    fn add(&mut self, dir: &Path, recursive: bool) -> Result<(), Error> {
+        let target = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if fs::canonicalize(target).map_or(false, |p| p == self.out_dir) {
+            return Ok(());
+        }
+
+        let c_path = CString::new(target.as_os_str().as_bytes())?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, c_path.as_ptr(), MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("failed to watch {}", target.display()));
+        }
+        self.dirs.insert(wd, (dir.to_path_buf(), recursive));
+
+        if recursive {
+            for entry in fs::read_dir(target)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() && !is_skipped_dir(&entry.file_name()) {
+                    self.add(&dir.join(entry.file_name()), true)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits up to `timeout_ms` (or forever if negative) for events, and adds
+    /// the paths they name to `changed`. Returns `false` on timeout.
+    fn wait(&mut self, timeout_ms: i32, changed: &mut BTreeSet<PathBuf>) -> Result<bool, Error> {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+                0 => return Ok(false),
+                n if n > 0 => break,
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err).context("failed to wait for inotify events");
+                    }
+                }
+            }
+        }
+
+        let mut buf = [0u8; 64 * 1024];
+        let len = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut _, buf.len()) };
+        if len < 0 {
+            return Err(io::Error::last_os_error()).context("failed to read inotify events");
+        }
+
+        let header = mem::size_of::<libc::inotify_event>();
+        let mut offset = 0;
+        while offset + header <= len as usize {
+            // Events are packed, so they may not be aligned.
+            let event: libc::inotify_event =
+                unsafe { ptr::read_unaligned(buf.as_ptr().add(offset) as *const _) };
+            let name = &buf[offset + header..offset + header + event.len as usize];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            offset += header + event.len as usize;
+
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                eprintln!("warning: inotify queue overflowed, some changes were missed");
+                continue;
+            }
+            if event.mask & libc::IN_IGNORED != 0 {
+                self.dirs.remove(&event.wd);
+                continue;
+            }
+            let (dir, recursive) = match self.dirs.get(&event.wd) {
+                Some((dir, recursive)) => (dir.clone(), *recursive),
+                None => continue,
+            };
+            let path = dir.join(OsStr::from_bytes(name));
+
+            if event.mask & libc::IN_ISDIR != 0 {
+                // Files in a new directory may have been written before it
+                // was watched.
+                if recursive && !is_skipped_dir(OsStr::from_bytes(name)) {
+                    self.add(&path, true)?;
+                    let walk = walkdir::WalkDir::new(&path).into_iter();
+                    let walked = |entry: &walkdir::DirEntry| {
+                        !(entry.file_type().is_dir() && is_skipped_dir(entry.file_name()))
+                    };
+                    for entry in walk.filter_entry(walked).flatten() {
+                        if entry.file_type().is_file() {
+                            changed.insert(entry.into_path());
+                        }
+                    }
+                }
+            } else if event.mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) != 0 {
+                changed.insert(path);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_watched_input() {
+    for input in &[".", "./"] {
+        let input = WatchedInput::new(input).unwrap();
+        assert!(matches!(input, WatchedInput::Dir(ref dir) if dir.as_os_str().is_empty()));
+        assert!(input.matches(Path::new("src/a.ts")));
+        assert!(!input.matches(Path::new("src/a.css")));
+    }
+
+    let file = WatchedInput::new("./src/missing.ts").unwrap();
+    assert!(matches!(file, WatchedInput::File(ref f) if f == Path::new("src/missing.ts")));
+    assert!(file.matches(Path::new("src/missing.ts")));
+
+    match WatchedInput::new("src/**/*.ts").unwrap() {
+        WatchedInput::Glob(ref pattern, ref base) => {
+            assert_eq!(base, Path::new("src"));
+            assert!(pattern.matches_path(Path::new("src/a/b.ts")));
+        }
+        _ => panic!("src/**/*.ts is not a glob input"),
+    }
+}
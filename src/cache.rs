@@ -0,0 +1,391 @@
+//! Persistent, content-addressed transform cache.
+//!
+//! Entries live in `<cacheDir>/v<CACHE_VERSION>/<key[..2]>/<key>.json`, where
+//! the key is a SHA-1 over everything the output depends on (see
+//! [cache_key]). Entries are written to a temporary file and renamed into
+//! place, so several worker processes can share one cache directory: readers
+//! either see a complete entry or none at all.
+
+use crate::{
+    swcrc,
+    transform::{enabled_passes, TransformOptions, TransformOutputWithRanges, PASS_VERSIONS},
+};
+#[cfg(feature = "node")]
+use crate::{get_compiler, panic::catch_panic, transform::get_options, util::MapErr};
+use anyhow::{Context as _, Error};
+#[cfg(feature = "node")]
+use napi::{CallContext, JsBuffer, JsObject, JsString};
+use serde::Serialize;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use swc::{try_with_handler, Compiler};
+use swc_common::FileName;
+
+/// Bump this when the layout of cache entries changes.
+const CACHE_VERSION: u32 = 1;
+
+/// Version of swc, read from `Cargo.lock` by the build script.
+pub const SWC_VERSION: &str = env!("SWC_VERSION");
+
+/// Used when `cacheMaxSize` is not set.
+const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+/// The cache directory is scanned for eviction once every this many writes.
+const EVICT_INTERVAL: usize = 64;
+
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Cache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Cache {
+    pub fn new(dir: &Path, max_size: Option<u64>) -> Self {
+        Cache {
+            dir: dir.join(format!("v{}", CACHE_VERSION)),
+            max_size: max_size.unwrap_or(DEFAULT_MAX_SIZE),
+        }
+    }
+
+    /// Returns the cache configured by `options`, if any.
+    /// Caching needs the digest of the options, so it is disabled for
+    /// options which were not deserialized from JSON.
+    pub fn from_options(options: &TransformOptions) -> Option<Self> {
+        options.options_digest.as_ref()?;
+        options
+            .cache_dir
+            .as_ref()
+            .map(|dir| Cache::new(dir, options.cache_max_size))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    /// Missing, unreadable and corrupt entries are all treated as a miss.
+    pub fn get(&self, key: &str) -> Option<TransformOutputWithRanges> {
+        let path = self.entry_path(key);
+        let bytes = fs::read(&path).ok()?;
+
+        match serde_json::from_slice(&bytes) {
+            Ok(output) => Some(output),
+            Err(_) => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, output: &TransformOutputWithRanges) -> Result<(), Error> {
+        let path = self.entry_path(key);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create cache directory {}", dir.display()))?;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let tmp = dir.join(format!("{}.{}.{}.tmp", key, process::id(), nanos));
+
+        let json = serde_json::to_vec(output).context("failed to serialize cache entry")?;
+        fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        if let Err(err) = fs::rename(&tmp, &path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err).with_context(|| format!("failed to write {}", path.display()));
+        }
+
+        if WRITES.fetch_add(1, Ordering::Relaxed) % EVICT_INTERVAL == 0 {
+            self.evict()?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the oldest entries until the cache fits in `max_size`.
+    fn evict(&self) -> Result<(), Error> {
+        let mut entries = vec![];
+        let mut total = 0;
+
+        for bucket in fs::read_dir(&self.dir)?.flatten() {
+            for entry in fs::read_dir(bucket.path())?.flatten() {
+                // Entries may be removed concurrently by other processes.
+                let meta = match entry.metadata() {
+                    Ok(meta) => meta,
+                    Err(_) => continue,
+                };
+                let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+                total += meta.len();
+                entries.push((modified, meta.len(), entry.path()));
+            }
+        }
+
+        if total <= self.max_size {
+            return Ok(());
+        }
+
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the cache key for compiling `src` with `options`.
+///
+/// The key covers the source, the options, the resolved filename, the
+/// `.swcrc` files which apply to the file, the addon and swc versions and the
+/// versions of the enabled passes. The options are covered by their digest,
+/// so there is no key for options without one.
+pub fn cache_key(src: &str, options: &TransformOptions) -> Option<String> {
+    let options_digest = options.options_digest.as_ref()?;
+
+    let mut hasher = Sha1::new();
+    let mut field = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+
+    field(&CACHE_VERSION.to_le_bytes());
+    field(env!("CARGO_PKG_VERSION").as_bytes());
+    field(SWC_VERSION.as_bytes());
+    field(src.as_bytes());
+
+    field(options_digest.as_bytes());
+    // Defaults which are read from the environment, not from the JSON.
+    field(options.swc.env_name.as_bytes());
+    field(options.swc.cwd.to_string_lossy().as_bytes());
+    // The digest is taken before `transformFile` sets the filename, and the
+    // output depends on it, e.g. the `sources` of the map.
+    field(options.swc.filename.as_bytes());
+
+    for path in swcrc::config_files(&options.swc) {
+        field(path.to_string_lossy().as_bytes());
+        field(&fs::read(&path).unwrap_or_default());
+    }
+
+    for (name, version) in enabled_passes(options) {
+        field(name.as_bytes());
+        field(&version.to_le_bytes());
+    }
+
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// Hex SHA-1 digest of `bytes`.
+pub fn digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(bytes))
+}
+
+/// Digest of options given as JSON, which does not depend on the order of
+/// their keys.
+pub fn options_digest(json: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(json) {
+        // Objects are sorted maps, so they are serialized with sorted keys.
+        Ok(value) => digest(value.to_string().as_bytes()),
+        Err(_) => digest(json),
+    }
+}
+
+/// Like [cache_key], but also resolves the config of the file through
+/// `Compiler::read_config`, the first step of `config_for_file`.
+///
+/// This is slower, as it reads and merges the `.swcrc`, but it reports
+/// `.swcrc` errors and gives ignored files a distinct key. The config itself
+/// is covered by the options and `.swcrc` files in [cache_key].
+pub fn resolved_cache_key(
+    c: &Compiler,
+    src: &str,
+    options: &TransformOptions,
+) -> Result<String, Error> {
+    let filename = if options.swc.filename.is_empty() {
+        FileName::Anon
+    } else {
+        FileName::Real(options.swc.filename.clone().into())
+    };
+
+    let ignored = try_with_handler(c.cm.clone(), |_handler| {
+        c.run(|| {
+            // `None` if `test` / `exclude` of the `.swcrc` ignore the file.
+            Ok(c.read_config(&options.swc, &filename)?.is_none())
+        })
+    })?;
+
+    let mut key = cache_key(src, options).context("the options have no digest")?;
+    if ignored {
+        key.push_str("ignored");
+    }
+
+    Ok(digest(key.as_bytes()))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Versions {
+    pub swc_wallaby: &'static str,
+    pub swc: &'static str,
+    pub cache: u32,
+    pub passes: BTreeMap<&'static str, u32>,
+}
+
+/// Versions of everything that goes into cache keys.
+pub fn versions() -> Versions {
+    Versions {
+        swc_wallaby: env!("CARGO_PKG_VERSION"),
+        swc: SWC_VERSION,
+        cache: CACHE_VERSION,
+        passes: PASS_VERSIONS.iter().cloned().collect(),
+    }
+}
+
+#[cfg(feature = "node")]
+#[js_function(3)]
+pub fn get_cache_key(cx: CallContext) -> napi::Result<JsString> {
+    let c = get_compiler(&cx);
+
+    let src = cx.get::<JsString>(0)?.into_utf8()?;
+    let filename = cx.get::<JsString>(1)?.into_utf8()?;
+    let mut options = get_options(&cx, 2)?;
+    if options.options_digest.is_none() {
+        options.options_digest = Some(options_digest(&cx.get::<JsBuffer>(2)?.into_value()?));
+    }
+    if !filename.as_str()?.is_empty() {
+        options.swc.filename = filename.as_str()?.to_string();
+    }
+
+    let src = src.as_str()?;
+    let key = catch_panic(&options.swc.filename, || resolved_cache_key(&c, src, &options))
+        .convert_err()?;
+
+    cx.env.create_string(&key)
+}
+
+#[cfg(feature = "node")]
+#[js_function]
+pub fn version(cx: CallContext) -> napi::Result<JsObject> {
+    cx.env.to_js_value(&versions())?.coerce_to_object()
+}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("swc-wallaby-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_cache_key() {
+    use crate::api::options_from_json;
+
+    let options = |json: &str| options_from_json(json).unwrap();
+    let a = options(r#"{"filename": "a.js", "swcrc": false}"#);
+
+    assert_eq!(cache_key("a", &a), cache_key("a", &a));
+    assert_ne!(cache_key("a", &a), cache_key("b", &a));
+    // The order of the keys does not matter.
+    let reordered = options(r#"{"swcrc": false, "filename": "a.js"}"#);
+    assert_eq!(cache_key("a", &a), cache_key("a", &reordered));
+
+    // Same options digest, as for one `transformFile` options object.
+    let mut b = options(r#"{"filename": "b.js", "swcrc": false}"#);
+    let mut a2 = a.clone();
+    a2.options_digest = Some("digest".into());
+    b.options_digest = Some("digest".into());
+    assert_ne!(cache_key("a", &a2), cache_key("a", &b));
+
+    let instrumented = options(r#"{"filename": "a.js", "swcrc": false, "instrument": true}"#);
+    assert_ne!(cache_key("a", &a), cache_key("a", &instrumented));
+
+    let undigested: TransformOptions = serde_json::from_str(r#"{"filename": "a.js"}"#).unwrap();
+    assert_eq!(cache_key("a", &undigested), None);
+}
+
+#[test]
+fn test_cache_get_put() {
+    let dir = test_dir("cache");
+    let cache = Cache::new(&dir, None);
+    let output = TransformOutputWithRanges {
+        code: "a;".into(),
+        ranges: vec![vec![1, 0, 1, 2]],
+        ..Default::default()
+    };
+
+    assert!(cache.get("abcd").is_none());
+    cache.put("abcd", &output).unwrap();
+    let cached = cache.get("abcd").unwrap();
+    assert_eq!(cached.code, "a;");
+    assert_eq!(cached.ranges, output.ranges);
+
+    // Corrupt entries are misses, and are removed.
+    fs::write(cache.entry_path("abce"), "{").unwrap();
+    assert!(cache.get("abce").is_none());
+    assert!(!cache.entry_path("abce").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_cache_evict() {
+    let dir = test_dir("evict");
+    let output = TransformOutputWithRanges {
+        code: "a;".into(),
+        ..Default::default()
+    };
+    Cache::new(&dir, None).put("aa01", &output).unwrap();
+    let size = fs::metadata(Cache::new(&dir, None).entry_path("aa01")).unwrap().len();
+
+    let cache = Cache::new(&dir, Some(size * 2));
+    for key in &["aa02", "ab03", "ac04"] {
+        cache.put(key, &output).unwrap();
+    }
+    cache.evict().unwrap();
+
+    let left = ["aa01", "aa02", "ab03", "ac04"]
+        .iter()
+        .filter(|key| cache.entry_path(key).exists())
+        .count();
+    assert_eq!(left, 2);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_resolved_cache_key() {
+    let dir = test_dir("resolved");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(".swcrc"), r#"{"jsc": {"target": "es5"}}"#).unwrap();
+
+    let key = |filename: &str| {
+        let json = serde_json::json!({
+            "filename": dir.join(filename),
+            "root": dir,
+        });
+        let options = crate::api::options_from_json(&json.to_string()).unwrap();
+        resolved_cache_key(&crate::compiler(), "a", &options).unwrap()
+    };
+
+    let a = key("a.ts");
+    assert_eq!(a, key("a.ts"));
+    assert_ne!(a, key("b.tsx"));
+
+    fs::write(dir.join(".swcrc"), r#"{"jsc": {"target": "es2019"}}"#).unwrap();
+    assert_ne!(a, key("a.ts"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,126 @@
+//! Cooperative cancellation of transforms.
+//!
+//! A transform is checked for cancellation between its phases: after
+//! loading the file, after parsing, after range collection, after swc's
+//! passes and after code generation. A phase which is already running is
+//! not interrupted.
+
+#[cfg(feature = "node")]
+use napi::{CallContext, JsExternal, JsUndefined, JsUnknown, ValueType};
+use std::{
+    error, fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+
+/// Error returned by a cancelled transform. It is mapped to
+/// `Status::Cancelled`, so the JS error has `code: 'Cancelled'`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cancelled {
+    /// The abort handle of the call was triggered.
+    Aborted,
+    TimedOut { timeout_ms: u64 },
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cancelled::Aborted => write!(f, "transform was aborted"),
+            Cancelled::TimedOut { timeout_ms } => {
+                write!(f, "transform timed out after {}ms", timeout_ms)
+            }
+        }
+    }
+}
+
+impl error::Error for Cancelled {}
+
+/// Abort flag and deadline of one transform.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    aborted: Option<Arc<AtomicBool>>,
+    deadline: Option<(Instant, u64)>,
+}
+
+impl CancelToken {
+    /// The timeout starts now, so time spent waiting for a worker thread
+    /// counts towards it.
+    pub fn new(aborted: Option<Arc<AtomicBool>>, timeout_ms: Option<u64>) -> Self {
+        CancelToken {
+            aborted,
+            deadline: timeout_ms.map(|ms| (Instant::now() + Duration::from_millis(ms), ms)),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if let Some(ref aborted) = self.aborted {
+            if aborted.load(Ordering::Relaxed) {
+                return Err(Cancelled::Aborted);
+            }
+        }
+
+        match self.deadline {
+            Some((deadline, timeout_ms)) if Instant::now() >= deadline => {
+                Err(Cancelled::TimedOut { timeout_ms })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reads the abort handle at `index`, which may be missing or `null`.
+#[cfg(feature = "node")]
+pub(crate) fn get_abort_handle(
+    cx: &CallContext,
+    index: usize,
+) -> napi::Result<Option<Arc<AtomicBool>>> {
+    if cx.length <= index {
+        return Ok(None);
+    }
+
+    let value = cx.get::<JsUnknown>(index)?;
+    if value.get_type()? != ValueType::External {
+        return Ok(None);
+    }
+
+    let handle = unsafe { value.cast::<JsExternal>() };
+    let aborted = cx.env.get_value_external::<Arc<AtomicBool>>(&handle)?;
+
+    Ok(Some(aborted.clone()))
+}
+
+#[cfg(feature = "node")]
+#[js_function]
+pub fn create_abort_handle(cx: CallContext) -> napi::Result<JsExternal> {
+    cx.env.create_external(Arc::new(AtomicBool::new(false)), None)
+}
+
+#[cfg(feature = "node")]
+#[js_function(1)]
+pub fn abort(cx: CallContext) -> napi::Result<JsUndefined> {
+    let handle = cx.get::<JsExternal>(0)?;
+    cx.env
+        .get_value_external::<Arc<AtomicBool>>(&handle)?
+        .store(true, Ordering::Relaxed);
+
+    cx.env.get_undefined()
+}
+
+#[test]
+fn test_cancel_token() {
+    let aborted = Arc::new(AtomicBool::new(false));
+    let token = CancelToken::new(Some(aborted.clone()), None);
+    assert_eq!(token.check(), Ok(()));
+    aborted.store(true, Ordering::Relaxed);
+    assert_eq!(token.check(), Err(Cancelled::Aborted));
+
+    let token = CancelToken::new(None, Some(0));
+    assert_eq!(token.check(), Err(Cancelled::TimedOut { timeout_ms: 0 }));
+}
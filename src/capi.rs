@@ -0,0 +1,211 @@
+//! C ABI, for hosts without Node such as JVM plugins or Python.
+//!
+//! Build the `cdylib` with
+//! `cargo build --release --no-default-features --features capi`, because the
+//! N-API bindings only load in Node. The declarations are in
+//! `include/swc_wallaby.h`, generated with `npm run build-header`.
+//!
+//! Requests and responses are JSON strings:
+//!
+//! - request: `{"src": "...", "options": {...}}` or
+//!   `{"file": "...", "options": {...}}`, with the options of `transform()`.
+//! - response: `{"result": {code, map, ranges, ...}}` with the output of
+//!   `transform()`, or `{"error": {"message": "...", "cancelled": bool}}`.
+//!
+//! All functions may be called from any thread, also concurrently. They share
+//! one compiler, and every call runs on its own thread like the N-API
+//! functions. Strings returned by this module are owned by the caller and
+//! must be released with [swc_wallaby_free_string].
+
+use crate::{
+    api::options_from_json,
+    cache::versions,
+    cancel::Cancelled,
+    compiler,
+    panic,
+    transform::{compile_file, compile_source, TransformOutputWithRanges},
+};
+use anyhow::{anyhow, bail, Context as _, Error};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_uint},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+    sync::Once,
+};
+
+/// Version of the C ABI. It changes when the signatures of the exported
+/// functions or the request or response shapes change incompatibly.
+pub const SWC_WALLABY_ABI_VERSION: c_uint = 1;
+
+static INSTALL_HOOK: Once = Once::new();
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    #[serde(default)]
+    src: Option<String>,
+    /// Read from disk instead of `src`.
+    #[serde(default)]
+    file: Option<PathBuf>,
+    #[serde(default)]
+    options: Option<Value>,
+}
+
+/// Returns [SWC_WALLABY_ABI_VERSION], so hosts can check that they load a
+/// compatible library.
+#[no_mangle]
+pub extern "C" fn swc_wallaby_abi_version() -> c_uint {
+    SWC_WALLABY_ABI_VERSION
+}
+
+/// Compiles the JSON `request` and returns the JSON response. Never returns
+/// null.
+///
+/// # Safety
+///
+/// `request` must be null or a NUL-terminated string, which is only read
+/// during the call.
+#[no_mangle]
+pub unsafe extern "C" fn swc_wallaby_transform(request: *const c_char) -> *mut c_char {
+    let request = if request.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(request))
+    };
+
+    respond(|| {
+        let request = match request {
+            Some(request) => request.to_str().context("request is not UTF-8")?,
+            None => bail!("request is null"),
+        };
+        let output = transform(request)?;
+        Ok(serde_json::to_value(output)?)
+    })
+}
+
+/// Returns the versions of this library, swc and the passes as JSON, like
+/// `version()` in JS. Never returns null.
+#[no_mangle]
+pub extern "C" fn swc_wallaby_version() -> *mut c_char {
+    respond(|| Ok(serde_json::to_value(versions())?))
+}
+
+/// Releases a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library which was not
+/// released yet.
+#[no_mangle]
+pub unsafe extern "C" fn swc_wallaby_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+fn transform(request: &str) -> Result<TransformOutputWithRanges, Error> {
+    let request: Request = serde_json::from_str(request).context("invalid request")?;
+    let options = request.options.unwrap_or_else(|| json!({}));
+    let mut options = options_from_json(&options.to_string()).context("invalid options")?;
+    let c = compiler();
+
+    match (request.src, request.file) {
+        (Some(src), None) => compile_source(&c, src, &options),
+        (None, Some(file)) => {
+            options.set_default_filename(&file);
+            compile_file(&c, &file, &options)
+        }
+        _ => bail!("exactly one of `src` and `file` is required"),
+    }
+}
+
+/// Runs `op` and serializes its result or error as a response. Panics must
+/// not unwind into the host, so they are caught here too.
+fn respond<F>(op: F) -> *mut c_char
+where
+    F: FnOnce() -> Result<Value, Error>,
+{
+    INSTALL_HOOK.call_once(panic::install_hook);
+
+    let result = catch_unwind(AssertUnwindSafe(op))
+        .unwrap_or_else(|_| Err(anyhow!("panic in swc-wallaby")));
+    let response = match result {
+        Ok(result) => json!({ "result": result }),
+        Err(err) => json!({
+            "error": {
+                "message": format!("{:?}", err),
+                "cancelled": err.chain().any(|cause| cause.is::<Cancelled>()),
+            }
+        }),
+    };
+
+    // JSON escapes NUL, so this cannot fail.
+    CString::new(response.to_string()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[test]
+fn test_transform() {
+    unsafe fn call(request: &str) -> Value {
+        let request = CString::new(request).unwrap();
+        let response = swc_wallaby_transform(request.as_ptr());
+        let value = serde_json::from_str(CStr::from_ptr(response).to_str().unwrap()).unwrap();
+        swc_wallaby_free_string(response);
+        value
+    }
+
+    unsafe {
+        let ok = call(r#"{"src": "a?.b", "options": {"filename": "a.js", "swcrc": false}}"#);
+        assert!(ok["result"]["code"].is_string());
+        assert!(ok["result"]["ranges"].is_array());
+
+        let err = call(r#"{"options": {}}"#);
+        assert!(err["error"]["message"].as_str().unwrap().contains("exactly one"));
+        assert_eq!(err["error"]["cancelled"], false);
+
+        let err = swc_wallaby_transform(ptr::null());
+        assert!(CStr::from_ptr(err).to_str().unwrap().contains("request is null"));
+        swc_wallaby_free_string(err);
+    }
+}
+
+/// The exported functions match their declarations in the header, which
+/// hosts compile against.
+#[test]
+fn test_header() {
+    let header = include_str!("../include/swc_wallaby.h");
+    let declarations = [
+        "unsigned int swc_wallaby_abi_version(void);",
+        "char *swc_wallaby_transform(const char *request);",
+        "char *swc_wallaby_version(void);",
+        "void swc_wallaby_free_string(char *s);",
+    ];
+    for declaration in &declarations {
+        assert!(header.contains(declaration), "missing `{}`", declaration);
+    }
+    let define = format!("#define SWC_WALLABY_ABI_VERSION {}", SWC_WALLABY_ABI_VERSION);
+    assert!(header.contains(&define));
+
+    // The same signatures, checked by the compiler.
+    let abi_version: extern "C" fn() -> c_uint = swc_wallaby_abi_version;
+    let transform: unsafe extern "C" fn(*const c_char) -> *mut c_char = swc_wallaby_transform;
+    let version: extern "C" fn() -> *mut c_char = swc_wallaby_version;
+    let free_string: unsafe extern "C" fn(*mut c_char) = swc_wallaby_free_string;
+
+    assert_eq!(abi_version(), SWC_WALLABY_ABI_VERSION);
+    unsafe {
+        let response = version();
+        let value: Value = serde_json::from_slice(CStr::from_ptr(response).to_bytes()).unwrap();
+        assert!(value["result"]["swcWallaby"].is_string());
+        free_string(response);
+
+        let request = CString::new(r#"{"src": "a", "options": {"swcrc": false}}"#).unwrap();
+        let response = transform(request.as_ptr());
+        assert!(!response.is_null());
+        free_string(response);
+        free_string(ptr::null_mut());
+    }
+}
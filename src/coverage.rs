@@ -0,0 +1,365 @@
+//! Coverage instrumentation for the `instrument` option.
+//!
+//! The counters are placed on the collected ranges, so coverage reports the
+//! same code as Wallaby does. Every statement with a range becomes an
+//! istanbul statement, and every function with a range an istanbul function.
+//! Branches are not counted.
+//!
+//! The instrumented code registers its [FileCoverage] in
+//! `globalThis.__coverage__`, where Jest and nyc read it. Locations are those
+//! of the input, so the coverage needs no source map.
+
+use crate::{cache::digest, ranges::Ranges};
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use swc_common::{BytePos, SourceMap, Span, Spanned, DUMMY_SP};
+use swc_ecmascript::{
+    ast::*,
+    parser::{lexer::Lexer, Parser, StringInput, Syntax},
+    utils::drop_span,
+    visit::{Fold, FoldWith},
+};
+
+/// Version of the instrumentation, part of persistent cache keys.
+pub const COVERAGE_VERSION: u32 = 1;
+
+/// Name of the variable holding the [FileCoverage] of a module.
+const COVERAGE_VAR: &str = "__swc_wallaby_cov";
+
+/// Coverage of one file, in the format of `istanbul-lib-coverage`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileCoverage {
+    pub path: String,
+    pub statement_map: BTreeMap<usize, Location>,
+    pub fn_map: BTreeMap<usize, FunctionMapping>,
+    pub branch_map: BTreeMap<usize, serde_json::Value>,
+    pub s: BTreeMap<usize, u64>,
+    pub f: BTreeMap<usize, u64>,
+    pub b: BTreeMap<usize, Vec<u64>>,
+    /// Digest of the source, so modules which are evaluated again keep
+    /// counting into the same object.
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// A one-based line and zero-based column, like the ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionMapping {
+    pub name: String,
+    pub decl: Location,
+    pub loc: Location,
+    pub line: usize,
+}
+
+/// Adds counters for the `ranges` of `program`, which were collected from
+/// `src`, and returns the coverage they count into.
+pub fn instrument(
+    program: Program,
+    cm: &SourceMap,
+    filename: &str,
+    src: &str,
+    ranges: &Ranges,
+) -> Result<(Program, FileCoverage), Error> {
+    let mut index = HashMap::with_capacity(ranges.len());
+    for (i, range) in ranges.iter().enumerate() {
+        index.entry(range.clone()).or_insert(i);
+    }
+
+    let mut instrumenter = Instrumenter {
+        cm,
+        index,
+        coverage: FileCoverage {
+            path: filename.to_string(),
+            hash: digest(src.as_bytes()),
+            ..Default::default()
+        },
+        fn_name: None,
+    };
+    let program = program.fold_with(&mut instrumenter);
+    let coverage = instrumenter.coverage;
+
+    let header = header(&coverage)?;
+    let program = match program {
+        Program::Module(mut m) => {
+            let at = directives(&m.body, module_stmt);
+            m.body.insert(at, ModuleItem::Stmt(header));
+            Program::Module(m)
+        }
+        Program::Script(mut s) => {
+            let at = directives(&s.body, Some);
+            s.body.insert(at, header);
+            Program::Script(s)
+        }
+    };
+
+    Ok((program, coverage))
+}
+
+struct Instrumenter<'a> {
+    cm: &'a SourceMap,
+    /// Index of each collected range.
+    index: HashMap<Vec<usize>, usize>,
+    coverage: FileCoverage,
+    /// Name of the function which is folded next.
+    fn_name: Option<String>,
+}
+
+impl Instrumenter<'_> {
+    fn location(&self, span: Span) -> Option<Location> {
+        if span.is_dummy() {
+            return None;
+        }
+        let (lo, hi) = (self.cm.lookup_char_pos(span.lo()), self.cm.lookup_char_pos(span.hi()));
+        let range = vec![lo.line, lo.col.0, hi.line, hi.col.0];
+        if !self.index.contains_key(&range) {
+            return None;
+        }
+
+        Some(Location {
+            start: Position {
+                line: lo.line,
+                column: lo.col.0,
+            },
+            end: Position {
+                line: hi.line,
+                column: hi.col.0,
+            },
+        })
+    }
+
+    /// Allocates a statement counter for `span`, if it has a range.
+    fn statement(&mut self, span: Span) -> Option<Box<Expr>> {
+        let loc = self.location(span)?;
+        let id = self.coverage.statement_map.len();
+        self.coverage.statement_map.insert(id, loc);
+        self.coverage.s.insert(id, 0);
+
+        Some(counter("s", id))
+    }
+
+    /// Allocates a function counter for `span`, if it has a range.
+    fn function(&mut self, span: Span) -> Option<Box<Expr>> {
+        let name = self.fn_name.take();
+        let loc = self.location(span)?;
+        let id = self.coverage.fn_map.len();
+        self.coverage.fn_map.insert(
+            id,
+            FunctionMapping {
+                name: name.unwrap_or_else(|| format!("(anonymous_{})", id)),
+                decl: loc,
+                loc,
+                line: loc.start.line,
+            },
+        );
+        self.coverage.f.insert(id, 0);
+
+        Some(counter("f", id))
+    }
+
+    /// Inserts a counter before every item of `items` after the first `skip`
+    /// for which `span_of` returns a span with a range.
+    fn count_stmts<T, F>(&mut self, items: Vec<T>, skip: usize, span_of: F) -> Vec<T>
+    where
+        T: From<Stmt>,
+        F: Fn(&T) -> Option<Span>,
+    {
+        let mut out = Vec::with_capacity(items.len() * 2);
+        for (i, item) in items.into_iter().enumerate() {
+            if i >= skip {
+                if let Some(counter) = span_of(&item).and_then(|span| self.statement(span)) {
+                    out.push(T::from(expr_stmt(counter)));
+                }
+            }
+            out.push(item);
+        }
+
+        out
+    }
+}
+
+impl Fold for Instrumenter<'_> {
+    fn fold_module_items(&mut self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
+        let items = items.fold_children_with(self);
+        let skip = directives(&items, module_stmt);
+        self.count_stmts(items, skip, |item| match item {
+            ModuleItem::Stmt(stmt) => counted_span(stmt),
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+                decl: Decl::Var(var),
+                ..
+            })) if !var.declare => Some(var.span),
+            _ => None,
+        })
+    }
+
+    fn fold_stmts(&mut self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let stmts = stmts.fold_children_with(self);
+        let skip = directives(&stmts, Some);
+        self.count_stmts(stmts, skip, counted_span)
+    }
+
+    fn fold_fn_decl(&mut self, n: FnDecl) -> FnDecl {
+        self.fn_name = Some(n.ident.sym.to_string());
+        n.fold_children_with(self)
+    }
+
+    fn fold_class_method(&mut self, n: ClassMethod) -> ClassMethod {
+        if let PropName::Ident(ref key) = n.key {
+            self.fn_name = Some(key.sym.to_string());
+        }
+        n.fold_children_with(self)
+    }
+
+    fn fold_function(&mut self, n: Function) -> Function {
+        let counter = self.function(n.span);
+        let mut n = n.fold_children_with(self);
+
+        if let (Some(counter), Some(body)) = (counter, &mut n.body) {
+            let at = directives(&body.stmts, Some);
+            body.stmts.insert(at, expr_stmt(counter));
+        }
+        n
+    }
+
+    fn fold_arrow_expr(&mut self, n: ArrowExpr) -> ArrowExpr {
+        let counter = self.function(n.span);
+        let mut n = n.fold_children_with(self);
+
+        n.body = match n.body {
+            BlockStmtOrExpr::BlockStmt(mut body) => {
+                if let Some(counter) = counter {
+                    let at = directives(&body.stmts, Some);
+                    body.stmts.insert(at, expr_stmt(counter));
+                }
+                BlockStmtOrExpr::BlockStmt(body)
+            }
+            BlockStmtOrExpr::Expr(expr) => {
+                // The body expression counts as a statement.
+                let statement = self.statement(expr.span());
+                let mut exprs: Vec<_> = counter.into_iter().chain(statement).collect();
+                if exprs.is_empty() {
+                    BlockStmtOrExpr::Expr(expr)
+                } else {
+                    exprs.push(expr);
+                    BlockStmtOrExpr::Expr(Box::new(Expr::Paren(ParenExpr {
+                        span: DUMMY_SP,
+                        expr: Box::new(Expr::Seq(SeqExpr {
+                            span: DUMMY_SP,
+                            exprs,
+                        })),
+                    })))
+                }
+            }
+        };
+        n
+    }
+}
+
+/// The span under which the range collector records `stmt`, or `None` if it
+/// is not counted.
+fn counted_span(stmt: &Stmt) -> Option<Span> {
+    match stmt {
+        // Function declarations are hoisted, and types are removed.
+        Stmt::Decl(Decl::Fn(..))
+        | Stmt::Decl(Decl::TsInterface(..))
+        | Stmt::Decl(Decl::TsTypeAlias(..))
+        | Stmt::Decl(Decl::Var(VarDecl { declare: true, .. }))
+        | Stmt::Empty(..) => None,
+        // Expression statements have no range of their own.
+        Stmt::Expr(ExprStmt { expr, .. }) => Some(expr.span()),
+        _ => Some(stmt.span()),
+    }
+}
+
+fn module_stmt(item: &ModuleItem) -> Option<&Stmt> {
+    match item {
+        ModuleItem::Stmt(stmt) => Some(stmt),
+        _ => None,
+    }
+}
+
+/// Number of leading directives like `"use strict"`, which must stay first.
+fn directives<T, F>(stmts: &[T], as_stmt: F) -> usize
+where
+    F: Fn(&T) -> Option<&Stmt>,
+{
+    stmts
+        .iter()
+        .take_while(|item| {
+            matches!(
+                as_stmt(item),
+                Some(Stmt::Expr(ExprStmt { expr, .. })) if matches!(**expr, Expr::Lit(Lit::Str(..)))
+            )
+        })
+        .count()
+}
+
+fn expr_stmt(expr: Box<Expr>) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr,
+    })
+}
+
+/// `__swc_wallaby_cov.<kind>[id]++`
+fn counter(kind: &str, id: usize) -> Box<Expr> {
+    let counters = Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(Expr::Ident(Ident::new(COVERAGE_VAR.into(), DUMMY_SP)))),
+        prop: Box::new(Expr::Ident(Ident::new(kind.into(), DUMMY_SP))),
+        computed: false,
+    });
+
+    Box::new(Expr::Update(UpdateExpr {
+        span: DUMMY_SP,
+        op: UpdateOp::PlusPlus,
+        prefix: false,
+        arg: Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(counters)),
+            prop: Box::new(Expr::Lit(Lit::Num(Number {
+                span: DUMMY_SP,
+                value: id as f64,
+            }))),
+            computed: true,
+        })),
+    }))
+}
+
+/// Declares the coverage variable, reusing the object of an earlier
+/// evaluation of the same source.
+fn header(coverage: &FileCoverage) -> Result<Stmt, Error> {
+    let src = format!(
+        "var {var} = (function (g, data) {{
+            var coverage = g.__coverage__ || (g.__coverage__ = {{}});
+            var current = coverage[data.path];
+            return current && current.hash === data.hash ? current : (coverage[data.path] = data);
+        }})(typeof globalThis !== 'undefined' ? globalThis : this, {data});",
+        var = COVERAGE_VAR,
+        data = serde_json::to_string(coverage)?,
+    );
+
+    let lexer = Lexer::new(
+        Syntax::default(),
+        Default::default(),
+        StringInput::new(&src, BytePos(0), BytePos(src.len() as u32)),
+        None,
+    );
+    let mut script = Parser::new_from(lexer)
+        .parse_script()
+        .map_err(|err| anyhow!("failed to parse the coverage header: {:?}", err))?;
+
+    Ok(drop_span(script.body.remove(0)))
+}
@@ -0,0 +1,174 @@
+//! Dependency extraction for the `dependencies` option.
+//!
+//! The dependencies are collected from the parsed program, before any pass
+//! runs, so imports which TypeScript elides because they are only used as
+//! types are still reported. Only literal specifiers are collected, so
+//! `require(name)` and `import(name)` are not. `require` is recognized by
+//! name, also where it is shadowed.
+
+use serde::{Deserialize, Serialize};
+use swc_common::{SourceMap, Span, DUMMY_SP};
+use swc_ecmascript::{
+    ast::*,
+    visit::{Node, Visit, VisitWith},
+};
+
+/// Version of the dependency extraction, part of persistent cache keys.
+pub const DEPENDENCIES_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependency {
+    pub specifier: String,
+    pub kind: DependencyKind,
+    /// The dependency is only needed for types, e.g. `import type`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub type_only: bool,
+    /// The import, export or call, as
+    /// `[startLine, startColumn, endLine, endColumn]` like `ranges`.
+    pub range: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencyKind {
+    /// `import ... from 'x'`, `import 'x'` and `import x = require('x')`.
+    Import,
+    /// `export ... from 'x'`.
+    Export,
+    /// `require('x')`.
+    Require,
+    /// `import('x')`, and `import('x')` types.
+    DynamicImport,
+    /// A pattern of `import.meta.glob('./*.js')` and its variants, which is
+    /// not expanded.
+    Glob,
+}
+
+/// The dependencies of `program` in source order.
+pub fn collect_dependencies(program: &Program, cm: &SourceMap) -> Vec<Dependency> {
+    let mut collector = Collector {
+        cm,
+        dependencies: vec![],
+    };
+    program.visit_with(&Invalid { span: DUMMY_SP }, &mut collector);
+    collector.dependencies
+}
+
+struct Collector<'a> {
+    cm: &'a SourceMap,
+    dependencies: Vec<Dependency>,
+}
+
+impl Collector<'_> {
+    fn add(&mut self, specifier: &str, kind: DependencyKind, type_only: bool, span: Span) {
+        let (lo, hi) = (self.cm.lookup_char_pos(span.lo()), self.cm.lookup_char_pos(span.hi()));
+        self.dependencies.push(Dependency {
+            specifier: specifier.to_string(),
+            kind,
+            type_only,
+            range: vec![lo.line, lo.col.0, hi.line, hi.col.0],
+        });
+    }
+}
+
+impl Visit for Collector<'_> {
+    fn visit_import_decl(&mut self, n: &ImportDecl, _parent: &dyn Node) {
+        self.add(&n.src.value, DependencyKind::Import, n.type_only, n.span);
+    }
+
+    fn visit_named_export(&mut self, n: &NamedExport, _parent: &dyn Node) {
+        if let Some(ref src) = n.src {
+            self.add(&src.value, DependencyKind::Export, n.type_only, n.span);
+        }
+    }
+
+    fn visit_export_all(&mut self, n: &ExportAll, _parent: &dyn Node) {
+        self.add(&n.src.value, DependencyKind::Export, false, n.span);
+    }
+
+    fn visit_ts_import_equals_decl(&mut self, n: &TsImportEqualsDecl, _parent: &dyn Node) {
+        if let TsModuleRef::TsExternalModuleRef(ref module) = n.module_ref {
+            self.add(&module.expr.value, DependencyKind::Import, false, n.span);
+        }
+    }
+
+    fn visit_ts_import_type(&mut self, n: &TsImportType, _parent: &dyn Node) {
+        self.add(&n.arg.value, DependencyKind::DynamicImport, true, n.span);
+        n.visit_children_with(self)
+    }
+
+    fn visit_call_expr(&mut self, n: &CallExpr, _parent: &dyn Node) {
+        let kind = match n.callee {
+            ExprOrSuper::Expr(ref callee) => match &**callee {
+                Expr::Ident(ident) if &*ident.sym == "require" => Some(DependencyKind::Require),
+                // Dynamic imports are calls of an `import` identifier.
+                Expr::Ident(ident) if &*ident.sym == "import" => {
+                    Some(DependencyKind::DynamicImport)
+                }
+                Expr::Member(member) if is_import_meta_glob(member) => Some(DependencyKind::Glob),
+                _ => None,
+            },
+            ExprOrSuper::Super(..) => None,
+        };
+
+        match (kind, n.args.first()) {
+            (Some(DependencyKind::Glob), Some(arg)) => {
+                // One pattern or an array of them.
+                let patterns: Vec<String> = match &*arg.expr {
+                    Expr::Array(ArrayLit { elems, .. }) => elems
+                        .iter()
+                        .flatten()
+                        .filter_map(|elem| literal(&elem.expr))
+                        .collect(),
+                    expr => literal(expr).into_iter().collect(),
+                };
+                for pattern in patterns {
+                    self.add(&pattern, DependencyKind::Glob, false, n.span);
+                }
+            }
+            (Some(kind), Some(arg)) => {
+                if let Some(specifier) = literal(&arg.expr) {
+                    self.add(&specifier, kind, false, n.span);
+                }
+            }
+            _ => {}
+        }
+
+        n.visit_children_with(self)
+    }
+}
+
+/// `import.meta.glob`, `import.meta.globEager` and
+/// `import.meta.globEagerDefault`.
+fn is_import_meta_glob(member: &MemberExpr) -> bool {
+    let is_import_meta = match member.obj {
+        ExprOrSuper::Expr(ref obj) => match &**obj {
+            Expr::MetaProp(MetaPropExpr { meta, prop }) => {
+                &*meta.sym == "import" && &*prop.sym == "meta"
+            }
+            _ => false,
+        },
+        ExprOrSuper::Super(..) => false,
+    };
+
+    is_import_meta
+        && !member.computed
+        && matches!(
+            &*member.prop,
+            Expr::Ident(prop)
+                if matches!(&*prop.sym, "glob" | "globEager" | "globEagerDefault")
+        )
+}
+
+/// The value of a string, or of a template without expressions.
+fn literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(Lit::Str(s)) => Some(s.value.to_string()),
+        Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+            let quasi = tpl.quasis.first()?;
+            Some(quasi.cooked.as_ref().unwrap_or(&quasi.raw).value.to_string())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,132 @@
+//! Detection of generated and minified files.
+//!
+//! Bundles and minified files produce huge numbers of ranges and are not
+//! worth instrumenting. When `skipGenerated` is set, such files are compiled
+//! without collecting ranges or instrumenting them, with the reason in
+//! `skipped`.
+
+use crate::source_map::source_mapping_url;
+use serde::{Deserialize, Serialize};
+
+/// `skipGenerated` option: `true` for the default thresholds, or an object
+/// overriding some of them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SkipGeneratedOption {
+    Bool(bool),
+    Options(SkipGenerated),
+}
+
+impl SkipGeneratedOption {
+    pub fn enabled(&self) -> Option<SkipGenerated> {
+        match self {
+            SkipGeneratedOption::Bool(true) => Some(Default::default()),
+            SkipGeneratedOption::Bool(false) => None,
+            SkipGeneratedOption::Options(options) => Some(options.clone()),
+        }
+    }
+}
+
+impl Default for SkipGeneratedOption {
+    fn default() -> Self {
+        SkipGeneratedOption::Bool(false)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SkipGenerated {
+    /// Files with a longer line are treated as minified.
+    pub max_line_length: usize,
+    /// Files larger than this many bytes are skipped.
+    pub max_size: usize,
+    /// Skip files with a `@generated` marker in their header.
+    pub generated_header: bool,
+    /// Skip files whose `sourceMappingURL` names a bundle or minified file.
+    pub bundle_source_map: bool,
+}
+
+impl Default for SkipGenerated {
+    fn default() -> Self {
+        SkipGenerated {
+            max_line_length: 1000,
+            max_size: 1024 * 1024,
+            generated_header: true,
+            bundle_source_map: true,
+        }
+    }
+}
+
+/// Why a file was skipped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum SkipReason {
+    /// `line` (one-based) is `length` bytes long.
+    LongLine { line: usize, length: usize },
+    TooLarge { size: usize },
+    GeneratedHeader,
+    BundleSourceMap { url: String },
+}
+
+/// Only this many bytes at the start of a file are searched for `@generated`.
+const HEADER_SIZE: usize = 1024;
+
+/// Markers of bundles and minified files in `sourceMappingURL`s.
+const BUNDLE_MARKERS: &[&str] = &[".min.", "bundle", "chunk", "vendor"];
+
+/// Returns why `src` looks generated, if it does.
+pub fn detect(src: &str, options: &SkipGenerated) -> Option<SkipReason> {
+    if src.len() > options.max_size {
+        return Some(SkipReason::TooLarge { size: src.len() });
+    }
+
+    if options.generated_header {
+        let mut end = src.len().min(HEADER_SIZE);
+        while !src.is_char_boundary(end) {
+            end -= 1;
+        }
+        if src[..end].contains("@generated") {
+            return Some(SkipReason::GeneratedHeader);
+        }
+    }
+
+    if options.bundle_source_map {
+        if let Some(url) = source_mapping_url(src) {
+            if !url.starts_with("data:") && BUNDLE_MARKERS.iter().any(|m| url.contains(m)) {
+                return Some(SkipReason::BundleSourceMap { url: url.into() });
+            }
+        }
+    }
+
+    src.lines()
+        .enumerate()
+        .find(|(_, line)| line.len() > options.max_line_length)
+        .map(|(idx, line)| SkipReason::LongLine {
+            line: idx + 1,
+            length: line.len(),
+        })
+}
+
+#[test]
+fn test_detect() {
+    let options = SkipGenerated::default();
+
+    assert_eq!(detect("var a = 1;\nvar b = 2;\n", &options), None);
+    assert_eq!(
+        detect("// @generated by protoc\nvar a;", &options),
+        Some(SkipReason::GeneratedHeader)
+    );
+    assert_eq!(
+        detect("var a;\n//# sourceMappingURL=app.bundle.js.map", &options),
+        Some(SkipReason::BundleSourceMap {
+            url: "app.bundle.js.map".into()
+        })
+    );
+    assert_eq!(
+        detect(&format!("var a;\n{}", "a;".repeat(600)), &options),
+        Some(SkipReason::LongLine {
+            line: 2,
+            length: 1200
+        })
+    );
+}
@@ -0,0 +1,645 @@
+//! `hoistMocks`: moves mock calls of Jest and Vitest before the code which
+//! imports the mocked modules, like `babel-plugin-jest-hoist`.
+//!
+//! These statements are moved to the top of their block, or for the module
+//! after the directives and the imports of `@jest/globals` and `vitest`:
+//!
+//! - `jest.mock`, `jest.unmock`, `jest.deepUnmock`, `jest.enableAutomock`
+//!   and `jest.disableAutomock`, also chained,
+//! - `vi.mock` and `vi.unmock`,
+//! - `vi.hoisted(...)`, and declarations initialized with it.
+//!
+//! A mock factory runs before the module body, so it may only reference
+//! variables declared in the factory, variables named `mock*` (which are
+//! assumed to be read lazily), variables declared by `vi.hoisted`, imports of
+//! `@jest/globals` and `vitest`, and names which are not declared at the top
+//! level of the file. Other references are reported as errors.
+//!
+//! So a factory may use any undeclared name, where `babel-plugin-jest-hoist`
+//! only allows the globals of Node, and the variables of enclosing functions,
+//! which it reports, are not checked.
+//!
+//! swc's module transforms move the `require` calls of all imports to the top
+//! of the module, so [hoist_mocks_after_modules] moves the calls again after
+//! them, above the generated `require` calls. It keeps the `require` calls of
+//! `@jest/globals` and `vitest` first, and recognizes their `_globals.jest`
+//! form. It runs after the minifier too, so it splits the sequences and
+//! declarations which the minifier joins.
+
+use std::collections::HashSet;
+use swc_atoms::JsWord;
+use swc_common::{Spanned, DUMMY_SP};
+use swc_ecmascript::{
+    ast::*,
+    utils::HANDLER,
+    visit::{Fold, FoldWith, Node, Visit, VisitWith},
+};
+
+/// Version of the pass, part of persistent cache keys.
+pub const HOIST_MOCKS_VERSION: u32 = 3;
+
+/// Modules exporting `jest` or `vi`. Their imports are hoisted too, so the
+/// hoisted calls can use them.
+const FRAMEWORKS: &[&str] = &["@jest/globals", "vitest"];
+
+pub fn hoist_mocks() -> impl Fold {
+    HoistMocks {
+        check_factories: true,
+        ..Default::default()
+    }
+}
+
+/// Hoists the calls again after swc's module transforms. The factories were
+/// checked by [hoist_mocks] already.
+pub fn hoist_mocks_after_modules() -> impl Fold {
+    HoistMocks::default()
+}
+
+#[derive(Debug, Default)]
+struct HoistMocks {
+    check_factories: bool,
+    /// Names declared at the top level of the file.
+    bindings: HashSet<JsWord>,
+    /// Names declared in the file which factories may reference.
+    allowed: HashSet<JsWord>,
+}
+
+impl Fold for HoistMocks {
+    fn fold_module(&mut self, mut m: Module) -> Module {
+        m.body = self.split_items(m.body);
+        m.visit_with(&Invalid { span: DUMMY_SP }, &mut TopLevel::new(&mut self.bindings));
+
+        for item in &m.body {
+            match item {
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import)) if is_framework(import) => {
+                    for specifier in &import.specifiers {
+                        self.allowed.insert(local(specifier).sym.clone());
+                    }
+                }
+                ModuleItem::Stmt(stmt) => self.allow_decl(stmt),
+                _ => {}
+            }
+        }
+
+        m.fold_children_with(self)
+    }
+
+    fn fold_script(&mut self, mut s: Script) -> Script {
+        s.body = self.split_stmts(s.body);
+        s.visit_with(&Invalid { span: DUMMY_SP }, &mut TopLevel::new(&mut self.bindings));
+        for stmt in &s.body {
+            self.allow_decl(stmt);
+        }
+
+        s.fold_children_with(self)
+    }
+
+    fn fold_module_items(&mut self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
+        let items = self.split_items(items.fold_children_with(self));
+
+        let (mut head, mut imports, mut hoisted, mut rest) = (vec![], vec![], vec![], vec![]);
+        for item in items {
+            match item {
+                ModuleItem::Stmt(stmt)
+                    if rest.is_empty() && hoisted.is_empty() && is_directive(&stmt) =>
+                {
+                    head.push(ModuleItem::Stmt(stmt))
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import)) if is_framework(&import) => {
+                    imports.push(ModuleItem::ModuleDecl(ModuleDecl::Import(import)))
+                }
+                ModuleItem::Stmt(stmt) if is_framework_require(&stmt) => {
+                    imports.push(ModuleItem::Stmt(stmt))
+                }
+                ModuleItem::Stmt(stmt) if self.is_hoisted(&stmt) => {
+                    hoisted.push(ModuleItem::Stmt(stmt))
+                }
+                item => rest.push(item),
+            }
+        }
+
+        head.extend(imports);
+        head.extend(hoisted);
+        head.extend(rest);
+        head
+    }
+
+    fn fold_stmts(&mut self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let stmts = self.split_stmts(stmts.fold_children_with(self));
+
+        let (mut head, mut imports, mut hoisted, mut rest) = (vec![], vec![], vec![], vec![]);
+        for stmt in stmts {
+            if rest.is_empty() && hoisted.is_empty() && is_directive(&stmt) {
+                head.push(stmt);
+            } else if is_framework_require(&stmt) {
+                imports.push(stmt);
+            } else if self.is_hoisted(&stmt) {
+                hoisted.push(stmt);
+            } else {
+                rest.push(stmt);
+            }
+        }
+
+        head.extend(imports);
+        head.extend(hoisted);
+        head.extend(rest);
+        head
+    }
+}
+
+impl HoistMocks {
+    /// After the module transforms and the minifier, splits sequences and
+    /// declarations which contain hoisted calls or framework `require`s,
+    /// e.g. `var a = require('vitest'), b = require('./b')`.
+    fn split_stmts(&self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        if self.check_factories {
+            return stmts;
+        }
+
+        let mut split = Vec::with_capacity(stmts.len());
+        for stmt in stmts {
+            match split_stmt(&stmt) {
+                Some(parts)
+                    if parts
+                        .iter()
+                        .any(|part| is_framework_require(part) || self.is_hoisted(part)) =>
+                {
+                    split.extend(parts)
+                }
+                _ => split.push(stmt),
+            }
+        }
+        split
+    }
+
+    fn split_items(&self, items: Vec<ModuleItem>) -> Vec<ModuleItem> {
+        if self.check_factories {
+            return items;
+        }
+
+        let mut split = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                ModuleItem::Stmt(stmt) => {
+                    split.extend(self.split_stmts(vec![stmt]).into_iter().map(ModuleItem::Stmt))
+                }
+                item => split.push(item),
+            }
+        }
+        split
+    }
+
+    /// Allows the names declared by `const x = vi.hoisted(...)` and
+    /// `const x = require('@jest/globals')`.
+    fn allow_decl(&mut self, stmt: &Stmt) {
+        if let Stmt::Decl(Decl::Var(var)) = stmt {
+            if self.is_vi_hoisted_decl(var) || is_framework_require(stmt) {
+                let mut names = HashSet::new();
+                var.visit_with(&Invalid { span: DUMMY_SP }, &mut Bindings(&mut names));
+                self.allowed.extend(names);
+            }
+        }
+    }
+
+    fn is_hoisted(&self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Expr(ExprStmt { expr, .. }) => {
+                self.is_vi_hoisted(expr) || self.mock_call(expr).is_some()
+            }
+            Stmt::Decl(Decl::Var(var)) => self.is_vi_hoisted_decl(var),
+            _ => false,
+        }
+    }
+
+    /// Returns `jest` or `vi` if `expr` is a chain of hoisted calls on it,
+    /// and checks their factories.
+    fn mock_call(&self, expr: &Expr) -> Option<&'static str> {
+        let call = match expr {
+            Expr::Call(call) => call,
+            _ => return None,
+        };
+        let (obj, method) = match &call.callee {
+            ExprOrSuper::Expr(callee) => match &**callee {
+                Expr::Member(MemberExpr {
+                    obj: ExprOrSuper::Expr(obj),
+                    prop,
+                    computed: false,
+                    ..
+                }) => match &**prop {
+                    Expr::Ident(method) => (obj, &*method.sym),
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let framework = if self.is_framework_object(obj, "jest") {
+            "jest"
+        } else if self.is_framework_object(obj, "vi") {
+            "vi"
+        } else {
+            self.mock_call(obj)?
+        };
+
+        let args = &call.args;
+        let hoisted = match (framework, method) {
+            ("jest", "mock") | ("vi", "mock") => match args.len() {
+                1 => is_str(&args[0].expr),
+                2 | 3 => {
+                    if self.check_factories {
+                        self.check_factory(framework, &args[1].expr);
+                    }
+                    true
+                }
+                _ => false,
+            },
+            ("jest", "unmock") | ("jest", "deepUnmock") | ("vi", "unmock") => {
+                args.len() == 1 && is_str(&args[0].expr)
+            }
+            ("jest", "enableAutomock") | ("jest", "disableAutomock") => args.is_empty(),
+            _ => false,
+        };
+
+        if hoisted {
+            Some(framework)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `expr` is the `jest` or `vi` object: the global, an import of
+    /// it, or `_globals.jest` for a `require` of the framework.
+    fn is_framework_object(&self, expr: &Expr, name: &str) -> bool {
+        match expr {
+            // A local `jest` or `vi` is not the test framework.
+            Expr::Ident(ident) => {
+                &*ident.sym == name
+                    && (!self.bindings.contains(&ident.sym) || self.allowed.contains(&ident.sym))
+            }
+            Expr::Member(MemberExpr {
+                obj: ExprOrSuper::Expr(obj),
+                prop,
+                computed: false,
+                ..
+            }) => {
+                matches!(&**obj, Expr::Ident(obj) if self.allowed.contains(&obj.sym))
+                    && matches!(&**prop, Expr::Ident(prop) if &*prop.sym == name)
+            }
+            _ => false,
+        }
+    }
+
+    /// `vi.hoisted(...)`, possibly awaited.
+    fn is_vi_hoisted(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Await(AwaitExpr { arg, .. }) => self.is_vi_hoisted(arg),
+            Expr::Call(CallExpr {
+                callee: ExprOrSuper::Expr(callee),
+                ..
+            }) => match &**callee {
+                Expr::Member(MemberExpr {
+                    obj: ExprOrSuper::Expr(obj),
+                    prop,
+                    computed: false,
+                    ..
+                }) => {
+                    self.is_framework_object(obj, "vi")
+                        && matches!(&**prop, Expr::Ident(prop) if &*prop.sym == "hoisted")
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn is_vi_hoisted_decl(&self, var: &VarDecl) -> bool {
+        matches!(&*var.decls, [VarDeclarator { init: Some(init), .. }] if self.is_vi_hoisted(init))
+    }
+
+    /// Reports references of `factory` to variables which may not be
+    /// initialized when it runs.
+    fn check_factory(&self, framework: &str, factory: &Expr) {
+        if !matches!(factory, Expr::Fn(..) | Expr::Arrow(..)) {
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(
+                        factory.span(),
+                        &format!(
+                            "The second argument of `{}.mock()` must be an inline function.",
+                            framework
+                        ),
+                    )
+                    .emit()
+            });
+            return;
+        }
+
+        let mut declared = HashSet::new();
+        factory.visit_with(&Invalid { span: DUMMY_SP }, &mut Bindings(&mut declared));
+        let mut references = References(vec![]);
+        factory.visit_with(&Invalid { span: DUMMY_SP }, &mut references);
+
+        for ident in references.0 {
+            let name = &ident.sym;
+            if declared.contains(name)
+                || !self.bindings.contains(name)
+                || self.allowed.contains(name)
+                || is_mock_name(name)
+            {
+                continue;
+            }
+
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(
+                        ident.span,
+                        &format!(
+                            "The module factory of `{}.mock()` is not allowed to reference any \
+                             out-of-scope variables.\nInvalid variable access: {}\nNote: This is \
+                             a precaution to guard against uninitialized mock variables. If it \
+                             is ensured that the mock is required lazily, variable names \
+                             prefixed with `mock` (case insensitive) are permitted.",
+                            framework, name
+                        ),
+                    )
+                    .emit()
+            });
+        }
+    }
+}
+
+fn is_framework(import: &ImportDecl) -> bool {
+    FRAMEWORKS.contains(&&*import.src.value)
+}
+
+fn local(specifier: &ImportSpecifier) -> &Ident {
+    match specifier {
+        ImportSpecifier::Named(s) => &s.local,
+        ImportSpecifier::Default(s) => &s.local,
+        ImportSpecifier::Namespace(s) => &s.local,
+    }
+}
+
+fn is_directive(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Expr(ExprStmt { expr, .. }) if matches!(**expr, Expr::Lit(Lit::Str(..))))
+}
+
+fn is_str(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(Lit::Str(..)) => true,
+        Expr::Tpl(tpl) => tpl.exprs.is_empty(),
+        _ => false,
+    }
+}
+
+/// Names which are assumed to be read lazily, and the coverage counters.
+fn is_mock_name(name: &str) -> bool {
+    name.get(..4).map_or(false, |prefix| prefix.eq_ignore_ascii_case("mock"))
+        || name.starts_with("cov")
+        || name.starts_with("__cov")
+        || name.starts_with("__swc_wallaby_cov")
+}
+
+/// The expressions of a sequence, or the declarators of a declaration, as
+/// separate statements.
+fn split_stmt(stmt: &Stmt) -> Option<Vec<Stmt>> {
+    match stmt {
+        Stmt::Expr(ExprStmt { span, expr }) => match &**expr {
+            Expr::Seq(seq) => Some(
+                seq.exprs
+                    .iter()
+                    .map(|expr| {
+                        Stmt::Expr(ExprStmt {
+                            span: *span,
+                            expr: expr.clone(),
+                        })
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        },
+        Stmt::Decl(Decl::Var(var)) if var.decls.len() > 1 => Some(
+            var.decls
+                .iter()
+                .map(|decl| {
+                    Stmt::Decl(Decl::Var(VarDecl {
+                        span: var.span,
+                        kind: var.kind,
+                        declare: var.declare,
+                        decls: vec![decl.clone()],
+                    }))
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// `var _globals = require('@jest/globals')`, also wrapped in an interop
+/// helper like `_interopRequireWildcard(require('vitest'))`.
+fn is_framework_require(stmt: &Stmt) -> bool {
+    fn is_require(expr: &Expr) -> bool {
+        match expr {
+            Expr::Call(CallExpr {
+                callee: ExprOrSuper::Expr(callee),
+                args,
+                ..
+            }) => match (&**callee, &**args) {
+                (Expr::Ident(callee), [arg]) if &*callee.sym == "require" => match &*arg.expr {
+                    Expr::Lit(Lit::Str(src)) => FRAMEWORKS.contains(&&*src.value),
+                    _ => false,
+                },
+                (_, [arg]) => is_require(&arg.expr),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    match stmt {
+        Stmt::Decl(Decl::Var(var)) => matches!(
+            &*var.decls,
+            [VarDeclarator { init: Some(init), .. }] if is_require(init)
+        ),
+        _ => false,
+    }
+}
+
+/// Collects the names declared in a node.
+struct Bindings<'a>(&'a mut HashSet<JsWord>);
+
+impl Visit for Bindings<'_> {
+    fn visit_binding_ident(&mut self, n: &BindingIdent, _parent: &dyn Node) {
+        self.0.insert(n.id.sym.clone());
+    }
+
+    fn visit_assign_pat_prop(&mut self, n: &AssignPatProp, _parent: &dyn Node) {
+        self.0.insert(n.key.sym.clone());
+        n.visit_children_with(self)
+    }
+
+    fn visit_fn_decl(&mut self, n: &FnDecl, _parent: &dyn Node) {
+        self.0.insert(n.ident.sym.clone());
+        n.visit_children_with(self)
+    }
+
+    fn visit_fn_expr(&mut self, n: &FnExpr, _parent: &dyn Node) {
+        if let Some(ref ident) = n.ident {
+            self.0.insert(ident.sym.clone());
+        }
+        n.visit_children_with(self)
+    }
+
+    fn visit_class_decl(&mut self, n: &ClassDecl, _parent: &dyn Node) {
+        self.0.insert(n.ident.sym.clone());
+        n.visit_children_with(self)
+    }
+
+    fn visit_class_expr(&mut self, n: &ClassExpr, _parent: &dyn Node) {
+        if let Some(ref ident) = n.ident {
+            self.0.insert(ident.sym.clone());
+        }
+        n.visit_children_with(self)
+    }
+
+    fn visit_import_specifier(&mut self, n: &ImportSpecifier, _parent: &dyn Node) {
+        self.0.insert(local(n).sym.clone());
+    }
+
+    fn visit_ts_enum_decl(&mut self, n: &TsEnumDecl, _parent: &dyn Node) {
+        self.0.insert(n.id.sym.clone());
+    }
+}
+
+/// Collects the names declared at the top level of a module or script, also
+/// by `var` declarations in its blocks.
+struct TopLevel<'a> {
+    names: &'a mut HashSet<JsWord>,
+    /// The number of enclosing blocks and loops.
+    depth: usize,
+}
+
+impl<'a> TopLevel<'a> {
+    fn new(names: &'a mut HashSet<JsWord>) -> Self {
+        TopLevel { names, depth: 0 }
+    }
+
+    fn nested<N: VisitWith<Self>>(&mut self, n: &N) {
+        self.depth += 1;
+        n.visit_children_with(self);
+        self.depth -= 1;
+    }
+}
+
+impl Visit for TopLevel<'_> {
+    fn visit_var_decl(&mut self, n: &VarDecl, _parent: &dyn Node) {
+        if n.kind == VarDeclKind::Var || self.depth == 0 {
+            for decl in &n.decls {
+                decl.name.visit_with(n, &mut Bindings(self.names));
+            }
+        }
+    }
+
+    fn visit_fn_decl(&mut self, n: &FnDecl, _parent: &dyn Node) {
+        if self.depth == 0 {
+            self.names.insert(n.ident.sym.clone());
+        }
+    }
+
+    fn visit_class_decl(&mut self, n: &ClassDecl, _parent: &dyn Node) {
+        if self.depth == 0 {
+            self.names.insert(n.ident.sym.clone());
+        }
+    }
+
+    fn visit_import_specifier(&mut self, n: &ImportSpecifier, _parent: &dyn Node) {
+        self.names.insert(local(n).sym.clone());
+    }
+
+    fn visit_ts_enum_decl(&mut self, n: &TsEnumDecl, _parent: &dyn Node) {
+        self.names.insert(n.id.sym.clone());
+    }
+
+    fn visit_ts_module_decl(&mut self, n: &TsModuleDecl, _parent: &dyn Node) {
+        if let TsModuleName::Ident(ref id) = n.id {
+            self.names.insert(id.sym.clone());
+        }
+    }
+
+    fn visit_ts_import_equals_decl(&mut self, n: &TsImportEqualsDecl, _parent: &dyn Node) {
+        self.names.insert(n.id.sym.clone());
+    }
+
+    fn visit_block_stmt(&mut self, n: &BlockStmt, _parent: &dyn Node) {
+        self.nested(n)
+    }
+
+    fn visit_for_stmt(&mut self, n: &ForStmt, _parent: &dyn Node) {
+        self.nested(n)
+    }
+
+    fn visit_for_in_stmt(&mut self, n: &ForInStmt, _parent: &dyn Node) {
+        self.nested(n)
+    }
+
+    fn visit_for_of_stmt(&mut self, n: &ForOfStmt, _parent: &dyn Node) {
+        self.nested(n)
+    }
+
+    fn visit_switch_stmt(&mut self, n: &SwitchStmt, _parent: &dyn Node) {
+        self.nested(n)
+    }
+
+    fn visit_function(&mut self, _: &Function, _parent: &dyn Node) {}
+
+    fn visit_class(&mut self, _: &Class, _parent: &dyn Node) {}
+
+    fn visit_expr(&mut self, _: &Expr, _parent: &dyn Node) {}
+}
+
+/// Collects the identifiers read in a node, without types, property names
+/// and declarations.
+struct References(Vec<Ident>);
+
+impl Visit for References {
+    fn visit_expr(&mut self, n: &Expr, _parent: &dyn Node) {
+        if let Expr::Ident(ident) = n {
+            self.0.push(ident.clone());
+        }
+        n.visit_children_with(self)
+    }
+
+    fn visit_member_expr(&mut self, n: &MemberExpr, _parent: &dyn Node) {
+        n.obj.visit_with(n, self);
+        if n.computed {
+            n.prop.visit_with(n, self);
+        }
+    }
+
+    fn visit_prop(&mut self, n: &Prop, _parent: &dyn Node) {
+        if let Prop::Shorthand(ident) = n {
+            self.0.push(ident.clone());
+        }
+        n.visit_children_with(self)
+    }
+
+    fn visit_jsx_element_name(&mut self, n: &JSXElementName, _parent: &dyn Node) {
+        match n {
+            // Lowercase names are intrinsic elements.
+            JSXElementName::Ident(ident) if ident.sym.starts_with(char::is_uppercase) => {
+                self.0.push(ident.clone())
+            }
+            _ => n.visit_children_with(self),
+        }
+    }
+
+    fn visit_jsx_object(&mut self, n: &JSXObject, _parent: &dyn Node) {
+        match n {
+            JSXObject::Ident(ident) => self.0.push(ident.clone()),
+            _ => n.visit_children_with(self),
+        }
+    }
+
+    fn visit_ts_type(&mut self, _: &TsType, _parent: &dyn Node) {}
+}
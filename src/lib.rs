@@ -29,27 +29,53 @@ DEALINGS IN THE SOFTWARE.
 #![recursion_limit = "2048"]
 //#![deny(clippy::all)]
 
+#[cfg(feature = "node")]
 #[macro_use]
 extern crate napi_derive;
 /// Explicit extern crate to use allocator.
+#[cfg(feature = "node")]
 extern crate swc_node_base;
 
-use backtrace::Backtrace;
-use napi::{CallContext, Env, JsObject, JsUndefined};
-use std::{env, panic::set_hook, sync::Arc};
-use swc::{Compiler, TransformOutput};
-use swc_common::{self, sync::Lazy, FilePathMapping, SourceMap};
+#[cfg(feature = "node")]
 use crate::transform::TransformOutputWithRanges;
+#[cfg(feature = "node")]
+use napi::{CallContext, Env, JsObject};
+use std::sync::Arc;
+use swc::Compiler;
+#[cfg(feature = "node")]
+use swc::TransformOutput;
+use swc_common::{self, sync::Lazy, FilePathMapping, SourceMap};
 
 mod amp_attributes;
+pub mod api;
+pub mod automock;
+pub mod cache;
+// Also compiled for unit tests, so that `cargo test` covers the C ABI.
+#[cfg(any(feature = "capi", test))]
+pub mod capi;
+pub mod cancel;
+pub mod coverage;
+pub mod dependencies;
+pub mod generated;
+pub mod hoist_mocks;
 mod hook_optimizer;
+#[cfg(feature = "node")]
 mod minify;
 mod next_dynamic;
 pub mod next_ssg;
+pub mod panic;
+pub mod source_map;
+pub mod stack;
+pub mod stats;
 mod styled_jsx;
+pub mod swcrc;
+pub mod test_discovery;
+pub mod trace;
 pub mod transform;
 mod util;
 pub mod ranges;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 static COMPILER: Lazy<Arc<Compiler>> = Lazy::new(|| {
     let cm = Arc::new(SourceMap::new(FilePathMapping::empty()));
@@ -57,17 +83,33 @@ static COMPILER: Lazy<Arc<Compiler>> = Lazy::new(|| {
     Arc::new(Compiler::new(cm.clone()))
 });
 
+#[cfg(feature = "node")]
 #[module_exports]
 fn init(mut exports: JsObject) -> napi::Result<()> {
-    if cfg!(debug_assertions) || env::var("SWC_DEBUG").unwrap_or_default() == "1" {
-        set_hook(Box::new(|panic_info| {
-            let backtrace = Backtrace::new();
-            println!("Panic: {:?}\nBacktrace: {:?}", panic_info, backtrace);
-        }));
+    panic::install_hook();
+
+    if let Err(err) = trace::init_from_env() {
+        eprintln!("swc-wallaby: {:?}", err);
     }
 
     exports.create_named_method("transform", transform::transform)?;
     exports.create_named_method("transformSync", transform::transform_sync)?;
+    exports.create_named_method("transformFile", transform::transform_file)?;
+    exports.create_named_method("transformFileSync", transform::transform_file_sync)?;
+    exports.create_named_method("compileOptions", transform::compile_options)?;
+    exports.create_named_method("createAbortHandle", cancel::create_abort_handle)?;
+    exports.create_named_method("abort", cancel::abort)?;
+
+    exports.create_named_method("getCacheKey", cache::get_cache_key)?;
+    exports.create_named_method("version", cache::version)?;
+
+    exports.create_named_method("generateAutomock", automock::generate_automock_sync)?;
+
+    exports.create_named_method("getStats", stats::get_stats)?;
+    exports.create_named_method("resetStats", stats::reset_stats)?;
+
+    exports.create_named_method("initTracing", trace::init_tracing)?;
+    exports.create_named_method("flushTracing", trace::flush_tracing)?;
 
     exports.create_named_method("minify", minify::minify)?;
     exports.create_named_method("minifySync", minify::minify_sync)?;
@@ -75,20 +117,23 @@ fn init(mut exports: JsObject) -> napi::Result<()> {
     Ok(())
 }
 
-fn get_compiler(_ctx: &CallContext) -> Arc<Compiler> {
+/// The compiler shared by all calls in this process. Default options, like
+/// `cacheDir` for a `new Compiler()`, are merged into each call by index.js.
+pub fn compiler() -> Arc<Compiler> {
     COMPILER.clone()
 }
 
-#[js_function]
-fn construct_compiler(ctx: CallContext) -> napi::Result<JsUndefined> {
-    // TODO: Assign swc::Compiler
-    ctx.env.get_undefined()
+#[cfg(feature = "node")]
+fn get_compiler(_ctx: &CallContext) -> Arc<Compiler> {
+    compiler()
 }
 
+#[cfg(feature = "node")]
 pub fn complete_output(env: &Env, output: TransformOutput) -> napi::Result<JsObject> {
     env.to_js_value(&output)?.coerce_to_object()
 }
 
+#[cfg(feature = "node")]
 pub fn complete_output_with_ranges(env: &Env, output: TransformOutputWithRanges) -> napi::Result<JsObject> {
     env.to_js_value(&output)?.coerce_to_object()
 }
@@ -27,6 +27,7 @@ DEALINGS IN THE SOFTWARE.
 */
 use crate::{
   complete_output, get_compiler,
+  panic::catch_panic,
   util::{CtxtExt, MapErr},
 };
 use fxhash::FxHashMap;
@@ -68,6 +69,13 @@ impl MinifyTarget {
       }
     }
   }
+
+  fn filename(&self) -> &str {
+    match self {
+      MinifyTarget::Single(..) => "",
+      MinifyTarget::Map(codes) => codes.keys().next().map(|s| &**s).unwrap_or_default(),
+    }
+  }
 }
 
 impl Task for MinifyTask {
@@ -76,10 +84,14 @@ impl Task for MinifyTask {
   type JsValue = JsObject;
 
   fn compute(&mut self) -> napi::Result<Self::Output> {
-    try_with_handler(self.c.cm.clone(), |handler| {
-      let fm = self.code.to_file(self.c.cm.clone());
+    let (c, code, opts) = (&self.c, &self.code, &self.opts);
+
+    catch_panic(code.filename(), || {
+      try_with_handler(c.cm.clone(), |handler| {
+        let fm = code.to_file(c.cm.clone());
 
-      self.c.minify(fm, &handler, &self.opts)
+        c.minify(fm, &handler, opts)
+      })
     })
     .convert_err()
   }
@@ -108,10 +120,12 @@ pub fn minify_sync(cx: CallContext) -> napi::Result<JsObject> {
 
   let c = get_compiler(&cx);
 
-  let fm = code.to_file(c.cm.clone());
+  let output = catch_panic(code.filename(), || {
+    let fm = code.to_file(c.cm.clone());
 
-  let output =
-    try_with_handler(c.cm.clone(), |handler| c.minify(fm, &handler, &opts)).convert_err()?;
+    try_with_handler(c.cm.clone(), |handler| c.minify(fm, &handler, &opts))
+  })
+  .convert_err()?;
 
   complete_output(&cx.env, output)
 }
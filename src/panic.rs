@@ -0,0 +1,119 @@
+//! Turns panics in transforms into errors.
+//!
+//! A panic in a pass must not unwind into libuv or V8, which would abort the
+//! process. Every exported function runs its work in [catch_panic], which
+//! returns the panic as a [Panicked] error. The global compiler stays usable,
+//! because its state is either scoped to the call or behind locks which are
+//! not poisoned by a panic. This needs unwinding, so it does not apply to
+//! the wasm build, see `src/wasm.rs`.
+
+use anyhow::Error;
+use backtrace::Backtrace;
+use std::{
+    any::Any,
+    cell::RefCell,
+    env, error, fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+thread_local! {
+    /// Location and backtrace of the last panic on this thread, recorded by
+    /// the panic hook.
+    static LAST_PANIC: RefCell<Option<(Option<String>, Option<Backtrace>)>> = RefCell::new(None);
+}
+
+/// A panic caught by [catch_panic].
+#[derive(Debug)]
+pub struct Panicked {
+    pub message: String,
+    /// The file being compiled, if known.
+    pub filename: Option<String>,
+    pub location: Option<String>,
+    pub backtrace: Option<String>,
+}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panic")?;
+        if let Some(ref filename) = self.filename {
+            write!(f, " while compiling {}", filename)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(ref location) = self.location {
+            write!(f, "\n    at {}", location)?;
+        }
+        if let Some(ref backtrace) = self.backtrace {
+            write!(f, "\n{}", backtrace)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for Panicked {}
+
+/// Installs the panic hook. Backtraces are captured and printed in debug
+/// builds or with `SWC_DEBUG=1`. Otherwise panics are only reported through
+/// the returned errors.
+pub fn install_hook() {
+    let debug = cfg!(debug_assertions) || env::var("SWC_DEBUG").unwrap_or_default() == "1";
+
+    panic::set_hook(Box::new(move |panic_info| {
+        let location = panic_info.location().map(|l| l.to_string());
+        let backtrace = if debug {
+            let backtrace = Backtrace::new();
+            // Not stdout, which carries the protocol of the compile server.
+            eprintln!("Panic: {:?}\nBacktrace: {:?}", panic_info, backtrace);
+            Some(backtrace)
+        } else {
+            None
+        };
+
+        LAST_PANIC.with(|last| *last.borrow_mut() = Some((location, backtrace)));
+    }));
+}
+
+/// Runs `op`, returning a panic as a [Panicked] error for `filename`.
+pub fn catch_panic<T, F>(filename: &str, op: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    LAST_PANIC.with(|last| last.borrow_mut().take());
+
+    panic::catch_unwind(AssertUnwindSafe(op)).unwrap_or_else(|payload| {
+        let (location, backtrace) = LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_default();
+
+        Err(Error::new(Panicked {
+            message: panic_message(&*payload),
+            filename: if filename.is_empty() {
+                None
+            } else {
+                Some(filename.to_string())
+            },
+            location,
+            backtrace: backtrace.map(|b| format!("{:?}", b)),
+        }))
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".into()
+    }
+}
+
+#[test]
+fn test_catch_panic() {
+    let err = catch_panic::<(), _>("a.js", || panic!("Not implemented")).unwrap_err();
+    let panicked = err.downcast_ref::<Panicked>().unwrap();
+    assert_eq!(panicked.message, "Not implemented");
+    assert_eq!(panicked.filename.as_deref(), Some("a.js"));
+
+    assert_eq!(catch_panic("", || Ok(1)).unwrap(), 1);
+}
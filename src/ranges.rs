@@ -19,35 +19,81 @@ use swc_ecma_ast::*;
 use swc::ecmascript::visit::{as_folder, Folder};
 use swc_common::{pass::CompilerPass, Span};
 use swc_common::source_map::SourceMap;
+use crate::test_discovery::{TestCollector, TestNode};
 
 // For if we implemented it as a "pass".
 // pub fn range_collector() -> impl Fold + Visit {
 //     immutable_visit_as_folder(RangeCollector { ranges: vec![vec![]] } )
 // }
 
+/// Version of the range collection, part of persistent cache keys. Bump it
+/// when the collected ranges change.
+pub const RANGES_VERSION: u32 = 1;
+
 pub struct RangeCollector<'a> {
     pub spans: &'a mut Vec<Span>,
+    /// Number of visited nodes, including those without a span.
+    pub nodes: usize,
+    /// Current expression and statement nesting.
+    pub depth: usize,
+    /// Nodes nested deeper than this are not visited.
+    pub max_depth: usize,
+    pub too_deep: bool,
+    /// Set to also collect the test calls.
+    pub tests: Option<TestCollector>,
+}
+
+/// Result of walking a program for ranges.
+#[derive(Default)]
+pub struct Collected {
+    pub ranges: Ranges,
+    pub nodes: usize,
+    /// The program is nested deeper than `max_depth`, and the ranges are
+    /// incomplete.
+    pub too_deep: bool,
+    /// The test calls, if requested.
+    pub tests: Option<Vec<TestNode>>,
 }
 
 pub fn get_ranges(program: &Program, cm: Arc<SourceMap>) -> Ranges {
+    collect_ranges(program, cm, usize::MAX, false).ranges
+}
+
+pub fn collect_ranges(
+    program: &Program,
+    cm: Arc<SourceMap>,
+    max_depth: usize,
+    tests: bool,
+) -> Collected {
     // Create Wallaby ranges.
     // See: https://wallabyjs.com/docs/config/compilers.html#writing-a-custom-compiler
-    let ranges = {
-        let mut spans: Vec<Span> = vec![];
-        let mut visitor = RangeCollector { spans: &mut spans };
-        program.visit_with(&Invalid { span: DUMMY_SP }, &mut visitor);
-
-        let mut ranges: Ranges = vec![];
-        for span in spans {
-            let lo = cm.lookup_char_pos(span.lo());
-            let hi = cm.lookup_char_pos(span.hi());
-            let new_range = vec![lo.line, lo.col.0, hi.line, hi.col.0];
-            ranges.push(new_range);
-        }
-        ranges
+    let mut spans: Vec<Span> = vec![];
+    let mut visitor = RangeCollector {
+        spans: &mut spans,
+        nodes: 0,
+        depth: 0,
+        max_depth,
+        too_deep: false,
+        tests: if tests { Some(TestCollector::default()) } else { None },
     };
+    program.visit_with(&Invalid { span: DUMMY_SP }, &mut visitor);
+    let (nodes, too_deep) = (visitor.nodes, visitor.too_deep);
+    let tests = visitor.tests.take().map(|tests| tests.finish(&cm));
+
+    let mut ranges: Ranges = vec![];
+    for span in spans {
+        let lo = cm.lookup_char_pos(span.lo());
+        let hi = cm.lookup_char_pos(span.hi());
+        let new_range = vec![lo.line, lo.col.0, hi.line, hi.col.0];
+        ranges.push(new_range);
+    }
     // dbg!(&ranges);
-    ranges
+    Collected {
+        ranges,
+        nodes,
+        too_deep,
+        tests,
+    }
 }
 
 impl RangeCollector<'_> {
@@ -55,6 +101,7 @@ impl RangeCollector<'_> {
         where
             N: Spanned + fmt::Debug + swc_ecmascript::codegen::Node,
     {
+        self.nodes += 1;
         let span = node.span();
         if !span.is_dummy() {
             self.spans.push(span);
@@ -64,11 +111,27 @@ impl RangeCollector<'_> {
         where
             N: Spanned + fmt::Debug,
     {
+        self.nodes += 1;
         let span = node.span();
         if !span.is_dummy() {
             self.spans.push(span);
         }
     }
+
+    /// Visits the children of `node` one level deeper, unless that exceeds
+    /// `max_depth`.
+    fn nested<N>(&mut self, node: &N)
+        where
+            N: VisitWith<Self>,
+    {
+        if self.depth >= self.max_depth {
+            self.too_deep = true;
+            return;
+        }
+        self.depth += 1;
+        node.visit_children_with(self);
+        self.depth -= 1;
+    }
 }
 
 impl Visit for RangeCollector<'_> {
@@ -139,7 +202,15 @@ impl Visit for RangeCollector<'_> {
     }
     fn visit_call_expr(&mut self, n: &CallExpr, _parent: &dyn swc_ecmascript::visit::Node) {
         self.show("CallExpr", n);
-        n.visit_children_with(self)
+        match self.tests {
+            Some(ref mut tests) if tests.enter(n) => {
+                n.visit_children_with(self);
+                if let Some(ref mut tests) = self.tests {
+                    tests.exit();
+                }
+            }
+            _ => n.visit_children_with(self),
+        }
     }
     fn visit_catch_clause(&mut self, n: &CatchClause, _parent: &dyn swc_ecmascript::visit::Node) {
         self.show("CatchClause", n);
@@ -247,7 +318,7 @@ impl Visit for RangeCollector<'_> {
     }
     fn visit_expr(&mut self, n: &Expr, _parent: &dyn swc_ecmascript::visit::Node) {
         self.show("Expr", n);
-        n.visit_children_with(self)
+        self.nested(n)
     }
     fn visit_expr_or_spread(&mut self, n: &ExprOrSpread, _parent: &dyn swc_ecmascript::visit::Node) {
         self.show("ExprOrSpread", n);
@@ -541,6 +612,10 @@ impl Visit for RangeCollector<'_> {
         self.show("SpreadElement", n);
         n.visit_children_with(self)
     }
+    // Statements nest like expressions, e.g. `if` chains, but are not ranges.
+    fn visit_stmt(&mut self, n: &Stmt, _parent: &dyn swc_ecmascript::visit::Node) {
+        self.nested(n)
+    }
     fn visit_str(&mut self, n: &Str, _parent: &dyn swc_ecmascript::visit::Node) {
         self.show("Str", n);
         n.visit_children_with(self)
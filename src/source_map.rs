@@ -0,0 +1,348 @@
+//! Source maps of the input and the output.
+//!
+//! swc composes the output map with an input map given as JSON. This module
+//! finds that JSON: it is either passed by the caller or loaded from the
+//! `sourceMappingURL` comment of the file.
+//!
+//! The output map is reshaped according to the map options: `sourceRoot`,
+//! relative `sources`, `sourcesContent` and the format it is returned in.
+
+use anyhow::{bail, Context as _, Error};
+use path_clean::clean;
+use pathdiff::diff_paths;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use swc_common::{sourcemap, SourceFile};
+
+/// `inputSourceMap` option.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum InputSourceMapOption {
+    /// `false` disables loading input maps, `true` is the default.
+    Bool(bool),
+    /// A source map as JSON, or `"inline"` (same as `true`).
+    Str(String),
+    /// A source map object.
+    Object(serde_json::Value),
+}
+
+impl Default for InputSourceMapOption {
+    fn default() -> Self {
+        InputSourceMapOption::Bool(true)
+    }
+}
+
+/// Returns the input source map of `fm` as JSON, if there is one.
+///
+/// Without an explicit map, the last `sourceMappingURL` comment is used. It
+/// may be a base64 `data:` URL or a path relative to `filename`. Only an
+/// explicit map which is invalid is an error: a referenced map which is
+/// missing, unreadable or invalid is ignored, as such comments are often
+/// stale in vendored code.
+pub fn load_input_source_map(
+    fm: &SourceFile,
+    filename: &str,
+    option: &InputSourceMapOption,
+) -> Result<Option<String>, Error> {
+    let json = match option {
+        InputSourceMapOption::Bool(false) => return Ok(None),
+        InputSourceMapOption::Object(map) => serde_json::to_string(map)?,
+        InputSourceMapOption::Str(s) if s != "inline" => s.clone(),
+        _ => return Ok(referenced_map(fm, filename)),
+    };
+
+    sourcemap::SourceMap::from_slice(json.as_bytes()).context("invalid input source map")?;
+
+    Ok(Some(json))
+}
+
+/// The valid map of the `sourceMappingURL` comment of `fm`.
+fn referenced_map(fm: &SourceFile, filename: &str) -> Option<String> {
+    let json = load_url(source_mapping_url(&fm.src)?, filename).ok()??;
+    sourcemap::SourceMap::from_slice(json.as_bytes()).ok()?;
+
+    Some(json)
+}
+
+/// Finds the URL of the last `//# sourceMappingURL=` comment.
+pub(crate) fn source_mapping_url(src: &str) -> Option<&str> {
+    src.lines().rev().find_map(|line| {
+        let line = line.trim();
+        let comment = line
+            .strip_prefix("//# sourceMappingURL=")
+            .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+            .or_else(|| {
+                line.strip_prefix("/*# sourceMappingURL=")
+                    .and_then(|rest| rest.strip_suffix("*/"))
+            })?;
+        Some(comment.trim())
+    })
+}
+
+fn load_url(url: &str, filename: &str) -> Result<Option<String>, Error> {
+    if let Some(data) = url.strip_prefix("data:") {
+        let (media_type, payload) = match data.find(',') {
+            Some(idx) => (&data[..idx], &data[idx + 1..]),
+            None => bail!("malformed source map data URL"),
+        };
+        if !media_type.ends_with(";base64") {
+            bail!("only base64 source map data URLs are supported")
+        }
+
+        let bytes = base64::decode(payload).context("failed to decode source map data URL")?;
+        return Ok(Some(String::from_utf8(bytes)?));
+    }
+
+    let dir = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+    let path = dir.join(url);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let json = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read source map {}", path.display()))?;
+
+    Ok(Some(json))
+}
+
+/// How the output map is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceMapFormat {
+    /// A JSON string, like `@swc/core`.
+    String,
+    /// A source map object.
+    Object,
+    /// A source map object whose `mappings` are decoded into one array of
+    /// segments per generated line. Segments are
+    /// `[generatedColumn, sourceIndex, sourceLine, sourceColumn, nameIndex?]`
+    /// with absolute, zero-based values.
+    Decoded,
+}
+
+impl Default for SourceMapFormat {
+    fn default() -> Self {
+        SourceMapFormat::String
+    }
+}
+
+/// Options shaping the output map.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceMapOptions {
+    #[serde(default)]
+    pub source_map_format: SourceMapFormat,
+
+    /// Value of `sourceRoot`.
+    #[serde(default)]
+    pub source_root: Option<String>,
+
+    /// Make absolute `sources` relative to this directory.
+    #[serde(default)]
+    pub sources_relative_to: Option<PathBuf>,
+
+    /// Embed (`true`) or strip (`false`) `sourcesContent`. swc's default is
+    /// kept if this is not set.
+    #[serde(default)]
+    pub sources_content: Option<bool>,
+}
+
+impl SourceMapOptions {
+    fn is_default(&self) -> bool {
+        self.source_map_format == SourceMapFormat::String
+            && self.source_root.is_none()
+            && self.sources_relative_to.is_none()
+            && self.sources_content != Some(false)
+    }
+}
+
+/// Output map, serialized either as a JSON string or as an object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SourceMapOutput {
+    Json(String),
+    Object(Value),
+}
+
+/// Applies `options` to the JSON map produced by swc.
+pub fn shape_source_map(json: String, options: &SourceMapOptions) -> Result<SourceMapOutput, Error> {
+    if options.is_default() {
+        return Ok(SourceMapOutput::Json(json));
+    }
+
+    let mut map: Value = serde_json::from_str(&json).context("swc produced an invalid source map")?;
+    let obj = match map.as_object_mut() {
+        Some(obj) => obj,
+        None => bail!("swc produced an invalid source map"),
+    };
+
+    if let Some(ref root) = options.source_root {
+        obj.insert("sourceRoot".into(), Value::String(root.clone()));
+    }
+
+    if let Some(ref base) = options.sources_relative_to {
+        if let Some(Value::Array(sources)) = obj.get_mut("sources") {
+            for source in sources.iter_mut() {
+                if let Value::String(s) = source {
+                    *s = relative_source(s, base);
+                }
+            }
+        }
+    }
+
+    if options.sources_content == Some(false) {
+        obj.remove("sourcesContent");
+    }
+
+    if options.source_map_format == SourceMapFormat::Decoded {
+        let mappings = obj
+            .get("mappings")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let decoded = decode_mappings(mappings)?;
+        obj.insert("mappings".into(), serde_json::to_value(decoded)?);
+    }
+
+    match options.source_map_format {
+        SourceMapFormat::String => Ok(SourceMapOutput::Json(serde_json::to_string(&map)?)),
+        _ => Ok(SourceMapOutput::Object(map)),
+    }
+}
+
+fn relative_source(source: &str, base: &Path) -> String {
+    let path = Path::new(source);
+    if !path.is_absolute() {
+        return clean(source);
+    }
+
+    match diff_paths(path, base) {
+        // Source maps use forward slashes on every platform.
+        Some(rel) => clean(&rel.to_string_lossy()).replace('\\', "/"),
+        None => clean(source),
+    }
+}
+
+/// Decodes the base64 VLQ `mappings` of a source map.
+pub fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Vec<i64>>>, Error> {
+    let mut lines = vec![];
+    // Every field but the generated column is relative to the previous
+    // segment, across lines.
+    let mut state = [0i64; 5];
+
+    for line in mappings.split(';') {
+        let mut segments = vec![];
+        state[0] = 0;
+
+        for segment in line.split(',').filter(|s| !s.is_empty()) {
+            let fields = decode_vlq(segment)?;
+            if !matches!(fields.len(), 1 | 4 | 5) {
+                bail!("invalid source map segment `{}`", segment)
+            }
+
+            let mut decoded = Vec::with_capacity(fields.len());
+            for (i, field) in fields.into_iter().enumerate() {
+                state[i] += field;
+                decoded.push(state[i]);
+            }
+            segments.push(decoded);
+        }
+
+        lines.push(segments);
+    }
+
+    Ok(lines)
+}
+
+fn decode_vlq(segment: &str) -> Result<Vec<i64>, Error> {
+    let mut fields = vec![];
+    let mut value = 0i64;
+    let mut shift = 0;
+
+    for byte in segment.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => bail!("invalid base64 VLQ `{}`", segment),
+        } as i64;
+
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            continue;
+        }
+
+        let negative = value & 1 == 1;
+        value >>= 1;
+        fields.push(if negative { -value } else { value });
+        value = 0;
+        shift = 0;
+    }
+
+    if shift != 0 {
+        bail!("truncated base64 VLQ `{}`", segment)
+    }
+
+    Ok(fields)
+}
+
+#[test]
+fn test_decode_mappings() {
+    assert_eq!(
+        decode_mappings("AAAA,IAAI;AACA").unwrap(),
+        vec![vec![vec![0, 0, 0, 0], vec![4, 0, 0, 4]], vec![vec![0, 0, 1, 4]]]
+    );
+}
+
+#[test]
+fn test_source_mapping_url() {
+    assert_eq!(
+        source_mapping_url("a();\n//# sourceMappingURL=a.js.map\n"),
+        Some("a.js.map")
+    );
+    assert_eq!(
+        source_mapping_url("a();\n/*# sourceMappingURL=data:application/json;base64,e30= */"),
+        Some("data:application/json;base64,e30=")
+    );
+    assert_eq!(source_mapping_url("a();"), None);
+}
+
+#[test]
+fn test_load_input_source_map() {
+    use swc_common::{FileName, SourceMap};
+
+    let cm = SourceMap::default();
+    let load = |src: &str, option: &InputSourceMapOption| {
+        let fm = cm.new_source_file(FileName::Custom("a.js".into()), src.into());
+        load_input_source_map(&fm, "a.js", option)
+    };
+    let discover = InputSourceMapOption::default();
+
+    let map = r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"AAAA"}"#;
+    let src = format!(
+        "a();\n//# sourceMappingURL=data:application/json;base64,{}\n",
+        base64::encode(map)
+    );
+    assert_eq!(load(&src, &discover).unwrap().as_deref(), Some(map));
+
+    // Broken references are ignored.
+    for url in &[
+        "missing.js.map",
+        // `not json`
+        "data:application/json;base64,bm90IGpzb24=",
+        "data:application/json,{}",
+        "data:nothing",
+    ] {
+        let src = format!("a();\n//# sourceMappingURL={}\n", url);
+        assert_eq!(load(&src, &discover).unwrap(), None, "{}", url);
+    }
+
+    // An explicit map must be valid.
+    assert!(load("a();", &InputSourceMapOption::Str("not json".into())).is_err());
+}
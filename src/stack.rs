@@ -0,0 +1,202 @@
+//! Protection against stack overflows on deeply nested input.
+//!
+//! The parser, [RangeCollector](crate::ranges::RangeCollector) and swc's
+//! folds all recurse once per level of nesting. A stack overflow aborts the
+//! process, so transforms run on a thread with a large stack, and input
+//! nested deeper than that stack allows is rejected up front:
+//!
+//! - before parsing, by the bracket and operator chain depth of the source
+//!   (see [nesting_depth]), and
+//! - before swc's folds, by the depth of expressions and statements seen
+//!   during range collection.
+//!
+//! `wasm32-unknown-unknown` has no threads, so there transforms run on the
+//! main stack, whose size is set at link time to [WASM_STACK_SIZE] (see
+//! `.cargo/config`), and `stackSize` can only lower the limit.
+
+use anyhow::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use std::panic;
+use std::{error, fmt};
+
+/// Default stack size of transform threads, 256 MiB. Only the pages which are
+/// actually used are committed.
+pub const DEFAULT_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Stack size of the wasm build. Keep in sync with `-zstack-size` in
+/// `.cargo/config`.
+pub const WASM_STACK_SIZE: usize = 32 * 1024 * 1024;
+
+/// Conservative stack usage of one level of nesting across swc's folds and
+/// code generation.
+const BYTES_PER_LEVEL: usize = 16 * 1024;
+
+/// The input is nested deeper than the stack allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooDeeplyNested {
+    pub limit: usize,
+}
+
+impl fmt::Display for TooDeeplyNested {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "input too deeply nested (more than {} levels), increase `stackSize` to compile it",
+            self.limit
+        )
+    }
+}
+
+impl error::Error for TooDeeplyNested {}
+
+/// The maximum nesting depth for a stack of `stack_size` bytes.
+pub fn max_depth(stack_size: usize) -> usize {
+    if cfg!(target_arch = "wasm32") {
+        stack_size.min(WASM_STACK_SIZE) / BYTES_PER_LEVEL
+    } else {
+        stack_size / BYTES_PER_LEVEL
+    }
+}
+
+/// Runs `op` on a new thread with a stack of `stack_size` bytes, and waits
+/// for it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_with_stack<T, F>(stack_size: usize, op: F) -> Result<T, Error>
+where
+    T: Send,
+    F: FnOnce() -> Result<T, Error> + Send,
+{
+    crossbeam_utils::thread::scope(|scope| {
+        let handle = scope
+            .builder()
+            .name("swc-wallaby".into())
+            .stack_size(stack_size)
+            .spawn(|_| op())?;
+
+        handle
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    })
+    .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+/// Runs `op` on the calling thread, see the module docs.
+#[cfg(target_arch = "wasm32")]
+pub fn run_with_stack<T, F>(_stack_size: usize, op: F) -> Result<T, Error>
+where
+    T: Send,
+    F: FnOnce() -> Result<T, Error> + Send,
+{
+    op()
+}
+
+/// The maximum nesting depth of `src`, outside of strings and comments: the
+/// depth of `(`, `[` and `{`, plus the length of the operator chains like
+/// `a + b + ...` in each of them, which swc nests as deeply.
+///
+/// This is an approximation which is cheap to compute before parsing:
+/// brackets in regex literals are counted, the contents of template literals
+/// are skipped, and each run of operator characters, like `+` or `===`,
+/// counts as one level. A chain ends at `,`, `;` and at line breaks which are
+/// not next to an operator.
+pub fn nesting_depth(src: &str) -> usize {
+    let bytes = src.as_bytes();
+    // The chain lengths in the open brackets, and their sum.
+    let (mut chains, mut sum) = (vec![0usize], 0usize);
+    let (mut depth, mut max) = (0usize, 0usize);
+    // Whether the last token was an operator, and whether a line break
+    // follows a token which was not.
+    let (mut after_op, mut line_break) = (false, false);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte.is_ascii_whitespace() {
+            line_break |= byte == b'\n' && !after_op;
+            i += 1;
+            continue;
+        }
+        if byte == b'/' && matches!(bytes.get(i + 1), Some(&b'/') | Some(&b'*')) {
+            if bytes[i + 1] == b'/' {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            } else {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 2;
+            }
+            continue;
+        }
+
+        let chain = chains.last_mut().unwrap();
+        if line_break && !is_operator(byte) {
+            sum -= *chain;
+            *chain = 0;
+        }
+        line_break = false;
+        after_op = is_operator(byte);
+
+        match byte {
+            b'(' | b'[' | b'{' => {
+                depth += 1;
+                chains.push(0);
+            }
+            b')' | b']' | b'}' => {
+                depth = depth.saturating_sub(1);
+                if chains.len() > 1 {
+                    sum -= chains.pop().unwrap();
+                }
+            }
+            b',' | b';' => {
+                sum -= *chain;
+                *chain = 0;
+            }
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            // The first character of a run of operator characters.
+            _ if after_op && !bytes[..i].last().map_or(false, |&b| is_operator(b)) => {
+                *chain += 1;
+                sum += 1;
+            }
+            _ => {}
+        }
+        max = max.max(depth + sum);
+        i += 1;
+    }
+
+    max
+}
+
+/// Characters of operators, without `/`, which may start a regex or a
+/// comment, and `<` and `>`, which also delimit JSX and type arguments.
+fn is_operator(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'+' | b'-' | b'*' | b'%' | b'&' | b'|' | b'^' | b'=' | b'!' | b'?' | b'~'
+    )
+}
+
+#[test]
+fn test_nesting_depth() {
+    assert_eq!(nesting_depth("a((b), [c])"), 2);
+    assert_eq!(nesting_depth("a('(((', \"[[\", `{{`) // (((\n/* [[[ */"), 1);
+    assert_eq!(nesting_depth(&"[".repeat(10)), 10);
+
+    // Operator chains, which end at `;` and at line breaks between operands.
+    let chain = vec!["a"; 100].join(" + ");
+    assert_eq!(nesting_depth(&format!("x = {};", chain)), 100);
+    assert_eq!(nesting_depth(&format!("f({}, b)", chain)), 100);
+    assert_eq!(nesting_depth("(a + (b + c))"), 4);
+    assert_eq!(nesting_depth("a === b;\nc + d\ne + f"), 1);
+    assert_eq!(nesting_depth("a +\nb /* c */ +\nc\n+ d"), 3);
+}
@@ -0,0 +1,169 @@
+//! Timing and size metrics of transforms.
+//!
+//! Every transform records its metrics into process-wide totals, which are
+//! exposed through `getStats()`. The metrics of a single call are returned as
+//! `stats` when the `stats` option is set.
+
+#[cfg(feature = "node")]
+use napi::{CallContext, JsObject, JsUndefined};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Mutex,
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+use swc_common::sync::Lazy;
+
+/// Metrics of one transform. Times are wall time in milliseconds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformStats {
+    /// `.swcrc` resolution and parsing.
+    pub parse_ms: f64,
+    /// Range collection.
+    pub ranges_ms: f64,
+    /// The custom passes and the swc pass chain.
+    pub transform_ms: f64,
+    /// Code and source map generation.
+    #[serde(default)]
+    pub codegen_ms: f64,
+    pub total_ms: f64,
+    /// AST nodes visited during range collection.
+    pub nodes: usize,
+    pub ranges: usize,
+    /// Size of the generated code in bytes.
+    pub output_size: usize,
+    /// Size of the source map in bytes, if one was generated.
+    pub map_size: usize,
+    pub cache_hit: bool,
+}
+
+/// Totals over all transforms in this process.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateStats {
+    pub calls: u64,
+    pub cache_hits: u64,
+    pub parse_ms: f64,
+    pub ranges_ms: f64,
+    pub transform_ms: f64,
+    pub codegen_ms: f64,
+    pub total_ms: f64,
+    pub nodes: u64,
+    pub ranges: u64,
+    pub output_size: u64,
+    pub map_size: u64,
+}
+
+static TOTALS: Lazy<Mutex<AggregateStats>> = Lazy::new(Default::default);
+
+/// Measures the phases of one transform.
+pub struct Timer {
+    start: Instant,
+    last: Instant,
+}
+
+impl Timer {
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Timer {
+            start: now,
+            last: now,
+        }
+    }
+
+    /// Milliseconds since the previous lap.
+    pub fn lap(&mut self) -> f64 {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        to_ms(elapsed)
+    }
+
+    pub fn total(&self) -> f64 {
+        to_ms(self.start.elapsed())
+    }
+}
+
+fn to_ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl AggregateStats {
+    pub fn add(&mut self, stats: &TransformStats) {
+        self.calls += 1;
+        self.cache_hits += stats.cache_hit as u64;
+        self.parse_ms += stats.parse_ms;
+        self.ranges_ms += stats.ranges_ms;
+        self.transform_ms += stats.transform_ms;
+        self.codegen_ms += stats.codegen_ms;
+        self.total_ms += stats.total_ms;
+        self.nodes += stats.nodes as u64;
+        self.ranges += stats.ranges as u64;
+        self.output_size += stats.output_size as u64;
+        self.map_size += stats.map_size as u64;
+    }
+}
+
+/// Adds `stats` to the process-wide totals.
+pub fn record(stats: &TransformStats) {
+    TOTALS.lock().unwrap_or_else(|err| err.into_inner()).add(stats);
+}
+
+pub fn totals() -> AggregateStats {
+    TOTALS.lock().unwrap_or_else(|err| err.into_inner()).clone()
+}
+
+pub fn reset() {
+    *TOTALS.lock().unwrap_or_else(|err| err.into_inner()) = Default::default();
+}
+
+#[cfg(feature = "node")]
+#[js_function]
+pub fn get_stats(cx: CallContext) -> napi::Result<JsObject> {
+    cx.env.to_js_value(&totals())?.coerce_to_object()
+}
+
+#[cfg(feature = "node")]
+#[js_function]
+pub fn reset_stats(cx: CallContext) -> napi::Result<JsUndefined> {
+    reset();
+    cx.env.get_undefined()
+}
+
+#[test]
+fn test_aggregate_stats() {
+    let stats = TransformStats {
+        parse_ms: 1.0,
+        transform_ms: 2.0,
+        codegen_ms: 3.0,
+        nodes: 10,
+        cache_hit: true,
+        ..Default::default()
+    };
+    let mut totals = AggregateStats::default();
+    totals.add(&stats);
+    totals.add(&TransformStats::default());
+
+    assert_eq!(totals.calls, 2);
+    assert_eq!(totals.cache_hits, 1);
+    assert_eq!(totals.codegen_ms, 3.0);
+    assert_eq!(totals.nodes, 10);
+}
+
+#[test]
+fn test_reset() {
+    // Other tests record concurrently, but never this many nodes.
+    const NODES: usize = 1 << 40;
+    record(&TransformStats {
+        nodes: NODES,
+        ..Default::default()
+    });
+    assert!(totals().nodes >= NODES as u64);
+
+    reset();
+    assert!(totals().nodes < NODES as u64);
+}
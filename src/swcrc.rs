@@ -0,0 +1,252 @@
+//! Lookup of the `.swcrc` files swc reads for a file.
+//!
+//! This mirrors the search, including `rootMode` and the `configFile` which
+//! is used when there is no `.swcrc`, and the `test` / `exclude` matching
+//! done by `Compiler::config_for_file`, so that the config files can be
+//! hashed for cache keys and reported without building a pass chain.
+
+use anyhow::{Context as _, Error};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use swc::config::{ConfigFile, Options, RootMode};
+
+/// Returns the config files which apply to `opts.filename`, in the order swc
+/// reads them.
+///
+/// This is the explicit `configFile` (if any) followed by the nearest
+/// `.swcrc`, searching from the directory of the file up to `root`.
+pub fn config_files(opts: &Options) -> Vec<PathBuf> {
+    let mut files = vec![];
+
+    if let Some(ConfigFile::Str(ref s)) = opts.config_file {
+        files.push(PathBuf::from(s));
+    }
+
+    if opts.swcrc && !opts.filename.is_empty() {
+        if let Some(swcrc) = find_swcrc(opts, Path::new(&opts.filename)) {
+            files.push(swcrc);
+        }
+    }
+
+    files
+}
+
+/// Finds the nearest `.swcrc` for `path`, stopping at `root` unless
+/// `rootMode` is `upward`.
+pub fn find_swcrc(opts: &Options, path: &Path) -> Option<PathBuf> {
+    let root = opts
+        .root
+        .clone()
+        .unwrap_or_else(|| env::current_dir().unwrap_or_default());
+
+    let mut parent = path.parent();
+    while let Some(dir) = parent {
+        let swcrc = dir.join(".swcrc");
+        if swcrc.is_file() {
+            return Some(swcrc);
+        }
+
+        if dir == root && matches!(opts.root_mode, RootMode::Root) {
+            break;
+        }
+        parent = dir.parent();
+    }
+
+    None
+}
+
+/// Which `.swcrc` entry applied to a file, and why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwcrcReport {
+    pub file: PathBuf,
+    /// Entries in the order swc evaluated them. If the file is not ignored,
+    /// the last one is the entry which was used.
+    pub entries: Vec<EntryMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMatch {
+    /// Index into the `.swcrc` array, or `None` if it holds a single config.
+    pub index: Option<usize>,
+    pub matched: bool,
+    pub reason: MatchReason,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum MatchReason {
+    /// The entry has neither `test` nor `exclude`.
+    Unconditional,
+    /// `pattern` in `exclude` matched the file.
+    Excluded { pattern: String },
+    /// `pattern` in `test` matched the file.
+    Tested { pattern: String },
+    /// None of the `test` patterns matched the file.
+    NotTested { patterns: Vec<String> },
+}
+
+/// Explains which entry of the nearest `.swcrc`, or else of the
+/// `configFile`, applies to `opts.filename`.
+///
+/// Returns `None` if no config file is used for the file.
+pub fn report(opts: &Options) -> Result<Option<SwcrcReport>, Error> {
+    if opts.filename.is_empty() {
+        return Ok(None);
+    }
+    let filename = Path::new(&opts.filename);
+    let swcrc = if opts.swcrc {
+        find_swcrc(opts, filename)
+    } else {
+        None
+    };
+    let file = match (swcrc, &opts.config_file) {
+        (Some(file), _) => file,
+        (None, Some(ConfigFile::Str(config_file))) => PathBuf::from(config_file),
+        (None, _) => return Ok(None),
+    };
+
+    let json = fs::read_to_string(&file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let rc: Value = serde_json::from_str(&json)
+        .with_context(|| format!("failed to parse {}", file.display()))?;
+
+    let (configs, is_multi) = match rc {
+        Value::Array(configs) => (configs, true),
+        config => (vec![config], false),
+    };
+
+    let name = filename.to_string_lossy();
+    let mut entries = vec![];
+    for (index, config) in configs.iter().enumerate() {
+        let (matched, reason) = match_entry(config, &name)?;
+        entries.push(EntryMatch {
+            index: if is_multi { Some(index) } else { None },
+            matched,
+            reason,
+        });
+        if matched {
+            break;
+        }
+    }
+
+    Ok(Some(SwcrcReport { file, entries }))
+}
+
+/// Same semantics as `Config::matches`: `exclude` wins over `test`, and an
+/// entry without `test` matches every file which is not excluded.
+fn match_entry(config: &Value, filename: &str) -> Result<(bool, MatchReason), Error> {
+    if let Some(pattern) = first_match(&patterns(config.get("exclude")), filename)? {
+        return Ok((false, MatchReason::Excluded { pattern }));
+    }
+
+    match config.get("test") {
+        Some(test) => {
+            let patterns = patterns(Some(test));
+            match first_match(&patterns, filename)? {
+                Some(pattern) => Ok((true, MatchReason::Tested { pattern })),
+                None => Ok((false, MatchReason::NotTested { patterns })),
+            }
+        }
+        None => Ok((true, MatchReason::Unconditional)),
+    }
+}
+
+/// `test` and `exclude` are either a regex or an array of them.
+fn patterns(matcher: Option<&Value>) -> Vec<String> {
+    match matcher {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(items)) => items.iter().flat_map(|v| patterns(Some(v))).collect(),
+        _ => vec![],
+    }
+}
+
+fn first_match(patterns: &[String], filename: &str) -> Result<Option<String>, Error> {
+    for pattern in patterns {
+        let re = Regex::new(pattern).with_context(|| format!("invalid regex `{}`", pattern))?;
+        if re.is_match(filename) {
+            return Ok(Some(pattern.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[test]
+fn test_match_entry() {
+    use serde_json::json;
+
+    let entry = |config: Value, filename: &str| match_entry(&config, filename).unwrap();
+
+    assert!(matches!(entry(json!({}), "a.js"), (true, MatchReason::Unconditional)));
+    assert!(matches!(
+        entry(json!({"test": ["\\.ts$", "\\.tsx$"]}), "a.tsx"),
+        (true, MatchReason::Tested { pattern }) if pattern == "\\.tsx$"
+    ));
+    assert!(matches!(
+        entry(json!({"test": "\\.ts$"}), "a.js"),
+        (false, MatchReason::NotTested { patterns }) if patterns == ["\\.ts$"]
+    ));
+    // `exclude` wins over `test`.
+    assert!(matches!(
+        entry(json!({"test": "\\.ts$", "exclude": "\\.d\\.ts$"}), "a.d.ts"),
+        (false, MatchReason::Excluded { pattern }) if pattern == "\\.d\\.ts$"
+    ));
+    assert!(match_entry(&json!({"test": "("}), "a.js").is_err());
+}
+
+#[test]
+fn test_report() {
+    use serde_json::json;
+
+    let dir = env::temp_dir().join(format!("swc-wallaby-swcrc-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(
+        dir.join(".swcrc"),
+        r#"[{"test": "\\.ts$"}, {"exclude": "\\.test\\.js$"}, {}]"#,
+    )
+    .unwrap();
+    fs::write(dir.join("config.json"), r#"{"test": "\\.js$"}"#).unwrap();
+
+    let report_for = |options: Value| {
+        let options: Options = serde_json::from_value(options).unwrap();
+        report(&options).unwrap()
+    };
+    let reasons = |report: &SwcrcReport| -> Vec<_> {
+        report.entries.iter().map(|e| (e.index, e.matched)).collect()
+    };
+
+    let ts = report_for(json!({"filename": dir.join("sub/a.ts"), "root": dir})).unwrap();
+    assert_eq!(ts.file, dir.join(".swcrc"));
+    assert_eq!(reasons(&ts), vec![(Some(0), true)]);
+
+    let test = report_for(json!({"filename": dir.join("a.test.js"), "root": dir}));
+    assert_eq!(
+        reasons(&test.unwrap()),
+        vec![(Some(0), false), (Some(1), false), (Some(2), true)]
+    );
+
+    // The search stops at `root`, unless `rootMode` is `upward`, and the
+    // `configFile` is used without a `.swcrc`.
+    let sub = dir.join("sub");
+    assert!(report_for(json!({"filename": sub.join("a.ts"), "root": sub})).is_none());
+    let upward = report_for(json!({
+        "filename": sub.join("a.ts"), "root": sub, "rootMode": "upward"
+    }));
+    assert_eq!(upward.unwrap().file, dir.join(".swcrc"));
+    let config_file = report_for(json!({
+        "filename": sub.join("a.js"), "root": sub, "configFile": dir.join("config.json")
+    }))
+    .unwrap();
+    assert_eq!(config_file.file, dir.join("config.json"));
+    assert_eq!(reasons(&config_file), vec![(None, true)]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
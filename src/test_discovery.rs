@@ -0,0 +1,277 @@
+//! Test discovery for the `tests` option.
+//!
+//! The calls of `describe`, `suite`, `it` and `test` are found during range
+//! collection, so test files are walked once. Their `.only`, `.skip`, `.todo`
+//! and `.each` forms are recognized, as are the `fit`, `fdescribe`, `xit`,
+//! `xtest` and `xdescribe` aliases. Calls are recognized by name, so a local
+//! function called `test` is reported too.
+//!
+//! Wallaby's `//file.only` and `//file.skip` comments are found among the
+//! comments collected by the parser, so text in strings and templates which
+//! looks like them is not.
+
+use serde::{Deserialize, Serialize};
+use swc_common::{
+    comments::{Comment, SwcComments},
+    SourceFile, SourceMap, Span, Spanned,
+};
+use swc_ecmascript::ast::*;
+
+/// Version of the test discovery, part of persistent cache keys.
+pub const TESTS_VERSION: u32 = 1;
+
+/// The tests of a file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestTree {
+    /// The file has a `//file.only` comment.
+    pub only: bool,
+    /// The file has a `//file.skip` comment.
+    pub skip: bool,
+    pub tests: Vec<TestNode>,
+}
+
+impl TestTree {
+    /// `comments` are those collected while parsing `fm`, and possibly other
+    /// files.
+    pub fn new(comments: &SwcComments, fm: &SourceFile, tests: Vec<TestNode>) -> Self {
+        let (only, skip) = file_markers(comments, fm);
+        TestTree { only, skip, tests }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestNode {
+    pub kind: TestKind,
+    /// The value of a string or template name. Templates with expressions
+    /// are given as source, e.g. `` `adds ${a}` ``.
+    pub name: Option<String>,
+    /// The name is a template with expressions.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+    /// The call, as `[startLine, startColumn, endLine, endColumn]` like
+    /// `ranges`.
+    pub range: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modifier: Option<TestModifier>,
+    /// A `.each` call, which runs once per row of its table.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub each: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TestNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestKind {
+    /// `describe` or `suite`.
+    Suite,
+    /// `it` or `test`.
+    Test,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestModifier {
+    Only,
+    Skip,
+    Todo,
+}
+
+/// Builds the tree of test calls during range collection. Calls are entered
+/// before and exited after their arguments are visited.
+#[derive(Debug, Default)]
+pub struct TestCollector {
+    stack: Vec<Pending>,
+    roots: Vec<Pending>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    call: TestCall,
+    span: Span,
+    children: Vec<Pending>,
+}
+
+#[derive(Debug)]
+struct TestCall {
+    kind: TestKind,
+    modifier: Option<TestModifier>,
+    each: bool,
+    /// The name, if it is a string or template.
+    name: Option<Name>,
+}
+
+#[derive(Debug)]
+enum Name {
+    Str(String),
+    Template(Span),
+}
+
+impl TestCollector {
+    /// Starts a test node if `call` is a test call, and returns whether it
+    /// did. Every started node must be ended with [TestCollector::exit].
+    pub fn enter(&mut self, call: &CallExpr) -> bool {
+        match test_call(call) {
+            Some(test) => {
+                self.stack.push(Pending {
+                    call: test,
+                    span: call.span,
+                    children: vec![],
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn exit(&mut self) {
+        if let Some(node) = self.stack.pop() {
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => self.roots.push(node),
+            }
+        }
+    }
+
+    pub fn finish(self, cm: &SourceMap) -> Vec<TestNode> {
+        self.roots.into_iter().map(|node| node.finish(cm)).collect()
+    }
+}
+
+impl Pending {
+    fn finish(self, cm: &SourceMap) -> TestNode {
+        let (name, template) = match self.call.name {
+            Some(Name::Str(name)) => (Some(name), false),
+            Some(Name::Template(span)) => (cm.span_to_snippet(span).ok(), true),
+            None => (None, false),
+        };
+        let (lo, hi) = (cm.lookup_char_pos(self.span.lo()), cm.lookup_char_pos(self.span.hi()));
+
+        TestNode {
+            kind: self.call.kind,
+            name,
+            template,
+            range: vec![lo.line, lo.col.0, hi.line, hi.col.0],
+            modifier: self.call.modifier,
+            each: self.call.each,
+            children: self.children.into_iter().map(|node| node.finish(cm)).collect(),
+        }
+    }
+}
+
+/// Recognizes `it(name, fn)`, `it.only(name, fn)`, `it.each(table)(name, fn)`
+/// and `` describe.skip.each`table`(name, fn) ``.
+fn test_call(call: &CallExpr) -> Option<TestCall> {
+    let callee = match call.callee {
+        ExprOrSuper::Expr(ref callee) => &**callee,
+        ExprOrSuper::Super(..) => return None,
+    };
+
+    // `.each(table)` and `` .each`table` `` return the function to call.
+    let (callee, each) = match callee {
+        Expr::Call(CallExpr {
+            callee: ExprOrSuper::Expr(inner),
+            ..
+        }) => (strip_each(inner)?, true),
+        Expr::TaggedTpl(TaggedTpl { tag, .. }) => (strip_each(tag)?, true),
+        callee => (callee, false),
+    };
+
+    let mut modifier = None;
+    let mut callee = callee;
+    while let Expr::Member(MemberExpr {
+        obj: ExprOrSuper::Expr(obj),
+        prop,
+        computed: false,
+        ..
+    }) = callee
+    {
+        let prop = match &**prop {
+            Expr::Ident(prop) => prop,
+            _ => return None,
+        };
+        let m = match &*prop.sym {
+            "only" => TestModifier::Only,
+            "skip" => TestModifier::Skip,
+            "todo" => TestModifier::Todo,
+            _ => return None,
+        };
+        // The outermost modifier wins, e.g. `it.only.skip` is skipped.
+        modifier.get_or_insert(m);
+        callee = &**obj;
+    }
+
+    let base = match callee {
+        Expr::Ident(base) => base,
+        _ => return None,
+    };
+    let (kind, alias) = match &*base.sym {
+        "describe" | "suite" => (TestKind::Suite, None),
+        "fdescribe" => (TestKind::Suite, Some(TestModifier::Only)),
+        "xdescribe" => (TestKind::Suite, Some(TestModifier::Skip)),
+        "it" | "test" => (TestKind::Test, None),
+        "fit" => (TestKind::Test, Some(TestModifier::Only)),
+        "xit" | "xtest" => (TestKind::Test, Some(TestModifier::Skip)),
+        _ => return None,
+    };
+
+    Some(TestCall {
+        kind,
+        modifier: modifier.or(alias),
+        each,
+        name: call.args.first().and_then(|arg| name(&arg.expr)),
+    })
+}
+
+/// The object of a `.each` member expression.
+fn strip_each(expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::Member(MemberExpr {
+            obj: ExprOrSuper::Expr(obj),
+            prop,
+            computed: false,
+            ..
+        }) => match &**prop {
+            Expr::Ident(prop) if &*prop.sym == "each" => Some(&**obj),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn name(expr: &Expr) -> Option<Name> {
+    match expr {
+        Expr::Lit(Lit::Str(s)) => Some(Name::Str(s.value.to_string())),
+        Expr::Tpl(tpl) if tpl.exprs.is_empty() => {
+            let quasi = tpl.quasis.first()?;
+            let value = quasi.cooked.as_ref().unwrap_or(&quasi.raw);
+            Some(Name::Str(value.value.to_string()))
+        }
+        Expr::Tpl(tpl) => Some(Name::Template(tpl.span())),
+        Expr::Paren(ParenExpr { expr, .. }) => name(expr),
+        _ => None,
+    }
+}
+
+/// Whether `fm` has a `//file.only` or `//file.skip` comment, also written
+/// as `/* file.only */`.
+pub fn file_markers(comments: &SwcComments, fm: &SourceFile) -> (bool, bool) {
+    let in_file = |comment: &Comment| {
+        fm.start_pos <= comment.span.lo() && comment.span.hi() <= fm.end_pos
+    };
+
+    let (mut only, mut skip) = (false, false);
+    for entry in comments.leading.iter().chain(comments.trailing.iter()) {
+        for comment in entry.value().iter().filter(|comment| in_file(comment)) {
+            match comment.text.trim() {
+                "file.only" => only = true,
+                "file.skip" => skip = true,
+                _ => {}
+            }
+        }
+    }
+
+    (only, skip)
+}
@@ -0,0 +1,330 @@
+//! Chrome trace export of `tracing` spans and events.
+//!
+//! Tracing is off unless it is enabled through the `SWC_WALLABY_TRACE` env
+//! var (a file path) or `initTracing()`. Spans become complete (`"X"`) events
+//! and events become instant (`"i"`) events of the Chrome trace event format,
+//! which can be loaded in `chrome://tracing` or Perfetto.
+//!
+//! Events are either appended to a JSON array in a file, or passed to a JS
+//! callback as JSON strings. The closing `]` of the file is optional in that
+//! format, so the file is valid at any point. The file is flushed whenever a
+//! root span such as `transform` closes, because the process may exit
+//! without `flushTracing()`, e.g. with `SWC_WALLABY_TRACE`.
+//!
+//! Each custom pass and each phase of a transform has its own span. swc runs
+//! its own passes as one chain, which is traced as `passes`; with
+//! `SWC_WALLABY_TRACE_ALL=1`, the spans swc emits inside it are nested in
+//! there. swc's `debug` and `trace` callsites are compiled out of release
+//! builds, see `Cargo.toml`.
+
+#[cfg(feature = "node")]
+use crate::util::MapErr;
+use anyhow::{bail, Context as _, Error};
+#[cfg(feature = "node")]
+use napi::{
+    threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    CallContext, JsFunction, JsString, JsUndefined, JsUnknown, ValueType,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    cell::Cell,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use swc_common::sync::Lazy;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    Layer, Registry,
+};
+
+/// Timestamps are relative to this.
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+static NEXT_TID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static TID: Cell<u64> = Cell::new(NEXT_TID.fetch_add(1, Ordering::Relaxed));
+}
+
+pub enum Sink {
+    /// The writer, and whether an event was written yet.
+    File(Mutex<(BufWriter<File>, bool)>),
+    #[cfg(feature = "node")]
+    Callback(ThreadsafeFunction<String>),
+}
+
+impl Sink {
+    pub fn file(path: &Path) -> Result<Self, Error> {
+        let mut out = BufWriter::new(
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?,
+        );
+        out.write_all(b"[\n")?;
+
+        Ok(Sink::File(Mutex::new((out, false))))
+    }
+
+    fn write(&self, event: Value) {
+        match self {
+            Sink::File(file) => {
+                let mut file = file.lock().unwrap_or_else(|err| err.into_inner());
+                let (out, written) = &mut *file;
+                if *written {
+                    let _ = out.write_all(b",\n");
+                }
+                *written = true;
+                let _ = serde_json::to_writer(out, &event);
+            }
+            #[cfg(feature = "node")]
+            Sink::Callback(callback) => {
+                callback.call(Ok(event.to_string()), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        match self {
+            Sink::File(file) => {
+                let _ = file.lock().unwrap_or_else(|err| err.into_inner()).0.flush();
+            }
+            #[cfg(feature = "node")]
+            Sink::Callback(..) => {}
+        }
+    }
+}
+
+struct ChromeLayer {
+    sink: Arc<Sink>,
+    /// Also trace spans and events of other crates, e.g. swc.
+    all: bool,
+}
+
+/// Stored in the extensions of each span.
+struct SpanTiming {
+    start: Instant,
+    tid: u64,
+    args: Map<String, Value>,
+}
+
+impl<S> Layer<S> for ChromeLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        self.all || metadata.target().starts_with("swc_wallaby")
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut args = ArgsVisitor(Map::new());
+        attrs.record(&mut args);
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanTiming {
+                start: Instant::now(),
+                tid: TID.with(|tid| tid.get()),
+                args: args.0,
+            });
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                let mut args = ArgsVisitor(std::mem::take(&mut timing.args));
+                values.record(&mut args);
+                timing.args = args.0;
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut args = ArgsVisitor(Map::new());
+        event.record(&mut args);
+        let name = args
+            .0
+            .remove("message")
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_else(|| event.metadata().name().to_string());
+
+        self.sink.write(json!({
+            "name": name,
+            "cat": event.metadata().target(),
+            "ph": "i",
+            "s": "t",
+            "ts": micros(Instant::now()),
+            "pid": process::id(),
+            "tid": TID.with(|tid| tid.get()),
+            "args": args.0,
+        }));
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+        let is_root = span.parent().is_none();
+        let mut extensions = span.extensions_mut();
+        let timing = match extensions.remove::<SpanTiming>() {
+            Some(timing) => timing,
+            None => return,
+        };
+
+        self.sink.write(json!({
+            "name": span.name(),
+            "cat": span.metadata().target(),
+            "ph": "X",
+            "ts": micros(timing.start),
+            "dur": timing.start.elapsed().as_secs_f64() * 1_000_000.0,
+            "pid": process::id(),
+            "tid": timing.tid,
+            "args": timing.args,
+        }));
+        if is_root {
+            self.sink.flush();
+        }
+    }
+}
+
+fn micros(at: Instant) -> f64 {
+    at.saturating_duration_since(*START).as_secs_f64() * 1_000_000.0
+}
+
+struct ArgsVisitor(Map<String, Value>);
+
+impl Visit for ArgsVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+/// The sink of the installed subscriber.
+static SINK: Lazy<Mutex<Option<Arc<Sink>>>> = Lazy::new(Default::default);
+
+/// Installs the global subscriber. This can only be done once per process.
+pub fn install(sink: Sink, all: bool) -> Result<(), Error> {
+    let mut installed = SINK.lock().unwrap_or_else(|err| err.into_inner());
+    if installed.is_some() {
+        bail!("tracing is already enabled")
+    }
+
+    Lazy::force(&START);
+    let sink = Arc::new(sink);
+    let subscriber = Registry::default().with(ChromeLayer {
+        sink: sink.clone(),
+        all,
+    });
+    tracing::subscriber::set_global_default(subscriber)
+        .context("failed to install the tracing subscriber")?;
+    *installed = Some(sink);
+
+    Ok(())
+}
+
+/// Enables tracing if `SWC_WALLABY_TRACE` is set to a file path.
+/// `SWC_WALLABY_TRACE_ALL=1` also traces swc itself.
+pub fn init_from_env() -> Result<(), Error> {
+    let path = match std::env::var("SWC_WALLABY_TRACE") {
+        Ok(path) if !path.is_empty() => path,
+        _ => return Ok(()),
+    };
+    let all = std::env::var("SWC_WALLABY_TRACE_ALL").unwrap_or_default() == "1";
+
+    install(Sink::file(Path::new(&path))?, all)
+}
+
+/// Flushes buffered events of the file sink.
+pub fn flush() {
+    if let Some(ref sink) = *SINK.lock().unwrap_or_else(|err| err.into_inner()) {
+        sink.flush();
+    }
+}
+
+/// `initTracing(file, callback, all)`: `file` is a path or `null`, in which
+/// case each event is passed to `callback` as a JSON string.
+#[cfg(feature = "node")]
+#[js_function(3)]
+pub fn init_tracing(cx: CallContext) -> napi::Result<JsUndefined> {
+    let file = cx.get::<JsUnknown>(0)?;
+    let all = cx.get::<JsUnknown>(2)?.coerce_to_bool()?.get_value()?;
+
+    let sink = if file.get_type()? == ValueType::String {
+        let path = unsafe { file.cast::<JsString>() }.into_utf8()?;
+        Sink::file(Path::new(path.as_str()?)).convert_err()?
+    } else {
+        let callback = cx.get::<JsFunction>(1)?;
+        let mut tsfn = cx.env.create_threadsafe_function(
+            &callback,
+            0,
+            |ctx: ThreadSafeCallContext<String>| ctx.env.create_string(&ctx.value).map(|v| vec![v]),
+        )?;
+        // Tracing must not keep the process alive.
+        tsfn.unref(cx.env)?;
+        Sink::Callback(tsfn)
+    };
+
+    install(sink, all).convert_err()?;
+
+    cx.env.get_undefined()
+}
+
+#[cfg(feature = "node")]
+#[js_function]
+pub fn flush_tracing(cx: CallContext) -> napi::Result<JsUndefined> {
+    flush();
+    cx.env.get_undefined()
+}
+
+#[test]
+fn test_file_sink() {
+    let path = std::env::temp_dir().join(format!("swc-wallaby-trace-{}.json", process::id()));
+    let subscriber = Registry::default().with(ChromeLayer {
+        sink: Arc::new(Sink::file(&path).unwrap()),
+        all: false,
+    });
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("transform", filename = "a.js").entered();
+        tracing::info_span!("parse").in_scope(|| tracing::info!("parsed"));
+    });
+
+    // Flushed by closing `transform`, without `flush()`.
+    let json = std::fs::read_to_string(&path).unwrap();
+    let events: Vec<Value> = serde_json::from_str(&format!("{}]", json)).unwrap();
+    let names: Vec<_> = events.iter().map(|e| e["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["parsed", "parse", "transform"]);
+    assert_eq!(events[0]["ph"], "i");
+    assert_eq!(events[2]["ph"], "X");
+    assert_eq!(events[2]["args"]["filename"], "a.js");
+
+    let _ = std::fs::remove_file(&path);
+}
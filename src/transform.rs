@@ -28,34 +28,68 @@ DEALINGS IN THE SOFTWARE.
 
 use crate::{
     amp_attributes::amp_attributes,
-    complete_output, complete_output_with_ranges, get_compiler,
+    cache::{cache_key, Cache},
+    cancel::CancelToken,
+    coverage::{instrument, FileCoverage, COVERAGE_VERSION},
+    dependencies::{collect_dependencies, Dependency, DEPENDENCIES_VERSION},
+    generated::{detect, SkipGeneratedOption, SkipReason},
+    hoist_mocks::{hoist_mocks, hoist_mocks_after_modules, HOIST_MOCKS_VERSION},
     hook_optimizer::hook_optimizer,
     next_dynamic::next_dynamic,
     next_ssg::next_ssg,
+    panic::catch_panic,
+    stack::{max_depth, nesting_depth, run_with_stack, TooDeeplyNested, DEFAULT_STACK_SIZE},
     styled_jsx::styled_jsx,
+    test_discovery::{TestTree, TESTS_VERSION},
+    util::decode_source,
+};
+#[cfg(feature = "node")]
+use crate::{
+    cache::options_digest,
+    cancel::get_abort_handle,
+    complete_output, complete_output_with_ranges, get_compiler,
     util::{CtxtExt, MapErr},
 };
-use anyhow::{Context as _, Error};
-use napi::{CallContext, Env, JsBoolean, JsObject, JsString, Task};
+use anyhow::{bail, Context as _, Error};
+#[cfg(feature = "node")]
+use napi::{
+    CallContext, Env, JsBoolean, JsBuffer, JsExternal, JsObject, JsString, JsUnknown, Task,
+    ValueType,
+};
 use serde::Deserialize;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use swc::{try_with_handler, Compiler, TransformOutput};
-use swc_common::{chain, pass::Optional, FileName, SourceFile};
-use swc_ecmascript::ast::Program;
-use swc_ecmascript::transforms::pass::noop;
-
-use anyhow::bail;
+use swc_common::{
+    chain, comments::Comment, pass::Optional, sourcemap, BytePos, FileName, SourceFile,
+};
+use swc_ecmascript::{
+    ast::Program,
+    transforms::helpers::{Helpers, HELPERS},
+    utils::HANDLER,
+    visit::FoldWith,
+};
 
 /// Input to transform
 #[derive(Debug)]
 pub enum Input {
     /// Raw source code.
     Source(Arc<SourceFile>),
+    /// Path of a file which is read from disk on the worker thread.
+    File(PathBuf),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransformOptions {
+    // Declared before `swc`, so that these keys are not taken by swc's own
+    // options (e.g. `sourceRoot`).
+    #[serde(flatten)]
+    pub source_map: SourceMapOptions,
+
     #[serde(flatten)]
     pub swc: swc::config::Options,
 
@@ -64,43 +98,104 @@ pub struct TransformOptions {
 
     #[serde(default)]
     pub pages_dir: Option<PathBuf>,
+
+    /// Directory of the persistent transform cache. Caching is disabled if
+    /// this is not set.
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Size limit of `cache_dir` in bytes.
+    #[serde(default)]
+    pub cache_max_size: Option<u64>,
+
+    /// Source map of the input, as an object or JSON string. If this is not
+    /// set, maps referenced by a `sourceMappingURL` comment are loaded.
+    #[serde(default)]
+    pub input_source_map: InputSourceMapOption,
+
+    /// Return per-phase timings and sizes as `stats`.
+    #[serde(default)]
+    pub stats: bool,
+
+    /// Report which `.swcrc` entry matched or excluded the file.
+    #[serde(default)]
+    pub report_swcrc: bool,
+
+    /// Fail with a cancellation error if the transform takes longer than
+    /// this. The timeout is checked between phases.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+
+    /// Stack size of the transform thread in bytes, which also bounds the
+    /// nesting depth of the input. Defaults to 256 MiB.
+    #[serde(default)]
+    pub stack_size: Option<usize>,
+
+    /// Compile generated and minified files without collecting ranges,
+    /// tests and dependencies or instrumenting them, see [crate::generated].
+    #[serde(default)]
+    pub skip_generated: SkipGeneratedOption,
+
+    /// Move `jest.mock` and `vi.mock` calls above the imports, see
+    /// [crate::hoist_mocks].
+    #[serde(default)]
+    pub hoist_mocks: bool,
+
+    /// Add coverage counters on the ranges, and return the coverage object
+    /// they count into as `coverage`.
+    #[serde(default)]
+    pub instrument: bool,
+
+    /// Return the `describe` and `it` calls as `tests`, see
+    /// [crate::test_discovery].
+    #[serde(default)]
+    pub tests: bool,
+
+    /// Return the imports, re-exports and `require` calls as `dependencies`,
+    /// see [crate::dependencies].
+    #[serde(default)]
+    pub dependencies: bool,
+
+    /// Digest of the JSON these options were deserialized from, see
+    /// [crate::cache::options_digest].
+    #[serde(skip)]
+    pub options_digest: Option<String>,
 }
 
+#[cfg(feature = "node")]
 pub struct TransformTask {
     pub c: Arc<Compiler>,
     pub input: Input,
     pub options: TransformOptions,
+    pub cancel: CancelToken,
 }
 
-use crate::ranges::{get_ranges};
+use crate::ranges::{collect_ranges, get_ranges, Collected, RANGES_VERSION};
+use crate::stats::{self, Timer, TransformStats};
 
+#[cfg(feature = "node")]
 impl Task for TransformTask {
     type Output = TransformOutputWithRanges;
     type JsValue = JsObject;
 
     fn compute(&mut self) -> napi::Result<Self::Output> {
-        let res = try_with_handler(self.c.cm.clone(), |handler| {
-            self.c.run(|| match self.input {
-                Input::Source(ref s) => {
-                    let before_pass = noop();
-                    self.c.process_js_with_custom_pass(
-                        s.clone(),
-                        &handler,
-                        &self.options.swc,
-                        before_pass,
-                        noop(),
-                    )
-                }
+        let (c, input, options, cancel) = (&self.c, &self.input, &self.options, &self.cancel);
+
+        run_with_stack(options.stack_size(), || {
+            catch_panic(&options.swc.filename, || {
+                try_with_handler(c.cm.clone(), |handler| {
+                    c.run(|| {
+                        cancel.check()?;
+                        let fm = match input {
+                            Input::Source(ref s) => s.clone(),
+                            Input::File(ref path) => load_file(c, path)?,
+                        };
+                        process_file(c, fm, handler, options, cancel)
+                    })
+                })
             })
         })
-        .convert_err();
-        let val = res.unwrap();
-        let ranges = vec![vec![]];
-        Ok(TransformOutputWithRanges {
-            code: val.code,
-            map: val.map,
-            ranges,
-        })
+        .convert_err()
     }
 
     fn resolve(self, env: Env, result: Self::Output) -> napi::Result<Self::JsValue> {
@@ -109,6 +204,9 @@ impl Task for TransformTask {
 }
 
 /// returns `compiler, (src / path), options, plugin, callback`
+///
+/// The fourth argument is an optional abort handle.
+#[cfg(feature = "node")]
 pub fn schedule_transform<F>(cx: CallContext, op: F) -> napi::Result<JsObject>
 where
     F: FnOnce(&Arc<Compiler>, String, bool, TransformOptions) -> TransformTask,
@@ -117,39 +215,121 @@ where
 
     let s = cx.get::<JsString>(0)?.into_utf8()?.as_str()?.to_owned();
     let is_module = cx.get::<JsBoolean>(1)?;
-    let options: TransformOptions = cx.get_deserialized(2)?;
+    let options = get_options(&cx, 2)?;
+    let cancel = CancelToken::new(get_abort_handle(&cx, 3)?, options.timeout_ms);
 
-    let task = op(&c, s, is_module.get_value()?, options);
+    let mut task = op(&c, s, is_module.get_value()?, options);
+    task.cancel = cancel;
 
     cx.env.spawn(task).map(|t| t.promise_object())
 }
 
+/// Reads the `TransformOptions` at `index`.
+///
+/// This is either a handle created by `compileOptions`, `{handle, filename}`
+/// to use such a handle for another file, or a JSON buffer. For buffers, a
+/// digest of the raw JSON is kept if it is needed for cache keys.
+#[cfg(feature = "node")]
+pub(crate) fn get_options(cx: &CallContext, index: usize) -> napi::Result<TransformOptions> {
+    let value = cx.get::<JsUnknown>(index)?;
+    if value.get_type()? == ValueType::External {
+        let handle = unsafe { value.cast::<JsExternal>() };
+        let options = cx.env.get_value_external::<TransformOptions>(&handle)?;
+        return Ok(options.clone());
+    }
+    if value.get_type()? == ValueType::Object && !value.is_buffer()? {
+        let object = unsafe { value.cast::<JsObject>() };
+        let handle = object.get_named_property::<JsExternal>("handle")?;
+        let filename = object.get_named_property::<JsString>("filename")?;
+        let mut options = cx.env.get_value_external::<TransformOptions>(&handle)?.clone();
+        // The digest does not cover the filename, which cache keys add.
+        options.swc.filename = filename.into_utf8()?.as_str()?.to_owned();
+        return Ok(options);
+    }
+
+    let mut options: TransformOptions = cx.get_deserialized(index)?;
+
+    if options.cache_dir.is_some() {
+        let buffer = cx.get::<JsBuffer>(index)?.into_value()?;
+        options.options_digest = Some(options_digest(&buffer));
+    }
+
+    Ok(options)
+}
+
+/// Deserializes options once, so that repeated transforms with the same
+/// options skip JSON parsing.
+///
+/// Only the deserialized options are kept. The `BuiltConfig` cannot be
+/// reused across calls, because its passes are stateful and depend on the
+/// file being compiled.
+#[cfg(feature = "node")]
+#[js_function(1)]
+pub fn compile_options(cx: CallContext) -> napi::Result<JsExternal> {
+    let mut options: TransformOptions = cx.get_deserialized(0)?;
+    let buffer = cx.get::<JsBuffer>(0)?.into_value()?;
+    options.options_digest = Some(options_digest(&buffer));
+
+    cx.env.create_external(options, None)
+}
+
 use crate::ranges::Ranges;
 use serde::*;
+use crate::swcrc::{self, SwcrcReport};
+use crate::source_map::{
+    load_input_source_map, shape_source_map, InputSourceMapOption, SourceMapOptions,
+    SourceMapOutput,
+};
 use swc::common::errors::Handler;
-use swc::config::BuiltConfig;
+use swc::config::InputSourceMap;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct TransformOutputWithRanges {
     pub code: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub map: Option<String>,
+    pub map: Option<SourceMapOutput>,
     pub ranges: Ranges,
+    /// The file is ignored by `.swcrc`, and `code` is the original source.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignored: bool,
+    /// The file looks generated, and was compiled without ranges.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<SkipReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swcrc: Option<SwcrcReport>,
+    /// Set with `instrument`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<FileCoverage>,
+    /// Set with `tests`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests: Option<TestTree>,
+    /// Set with `dependencies`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<Vec<Dependency>>,
+    /// Not stored in the persistent cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<TransformStats>,
 }
 
+#[cfg(feature = "node")]
 pub fn exec_transform<F>(cx: CallContext, op: F) -> napi::Result<JsObject>
 where
-    F: FnOnce(&Compiler, String, &TransformOptions) -> Result<Arc<SourceFile>, Error>,
+    F: FnOnce(&Compiler, String, &TransformOptions) -> Result<Arc<SourceFile>, Error> + Send,
 {
     let c = get_compiler(&cx);
 
     let s = cx.get::<JsString>(0)?.into_utf8()?;
     let is_module = cx.get::<JsBoolean>(1)?;
-    let options: TransformOptions = cx.get_deserialized(2)?;
+    let options = get_options(&cx, 2)?;
 
     let str = s.as_str()?;
+    let is_module = is_module.get_value()?;
+    let filename = options.swc.filename.clone();
 
-    let output = my_transform(c, str, is_module.get_value()?, options, op).convert_err()?;
+    let output = run_with_stack(options.stack_size(), || {
+        catch_panic(&filename, || my_transform(c, str, is_module, options, op))
+    })
+    .convert_err()?;
 
     complete_output_with_ranges(cx.env, output)
 }
@@ -170,36 +350,338 @@ where
                 let program: Program =
                     serde_json::from_str(s).context("failed to deserialize Program")?;
                 let ranges: Ranges = get_ranges(&program, c.cm.clone());
-                let res = c.process_js(&handler, program, &options.swc).unwrap();
+                let res = c.process_js(&handler, program, &options.swc)?;
                 // let ranges: Ranges = vec![vec![0, 0, 0, 0]];
                 Ok(TransformOutputWithRanges {
                     code: res.code,
-                    map: res.map,
+                    map: shape_map(res.map, &options)?,
                     ranges,
+                    ..Default::default()
                 })
             } else {
                 let fm = op(&c, s.to_string(), &options).context("failed to load file")?;
-                let program = get_program(&c, fm, handler, &options)?;
-                let ranges: Ranges = get_ranges(&program, c.cm.clone());
-                let res = c.process_js(&handler, program, &options.swc).unwrap();
-                // let res = c.process_js_file(fm, &handler, &options.swc).unwrap();
-                Ok(TransformOutputWithRanges {
-                    code: res.code,
-                    map: res.map,
-                    ranges,
-                })
+                let cancel = CancelToken::new(None, options.timeout_ms);
+                process_file(&c, fm, handler, &options, &cancel)
             }
         })
     });
     output
 }
 
+/// Parses `fm`, collects its ranges and compiles it.
+///
+/// If `cacheDir` is set, the output is looked up in and stored to the
+/// persistent cache. `cancel` is checked between phases.
+pub fn process_file(
+    c: &Arc<Compiler>,
+    fm: Arc<SourceFile>,
+    handler: &Handler,
+    options: &TransformOptions,
+    cancel: &CancelToken,
+) -> Result<TransformOutputWithRanges, Error> {
+    let _span = tracing::info_span!("transform", filename = %options.swc.filename).entered();
+
+    let mut timer = Timer::start();
+    let mut stats = TransformStats::default();
+
+    let skipped = options
+        .skip_generated
+        .enabled()
+        .and_then(|skip| detect(&fm.src, &skip));
+
+    let cache = Cache::from_options(options)
+        .and_then(|cache| Some((cache_key(&fm.src, options)?, cache)));
+    if let Some((ref key, ref cache)) = cache {
+        if let Some(mut output) = cache.get(key) {
+            stats.cache_hit = true;
+            finish_stats(&mut output, stats, &timer, options);
+            return Ok(output);
+        }
+    }
+
+    let swcrc = if options.report_swcrc {
+        swcrc::report(&options.swc)?
+    } else {
+        None
+    };
+
+    let limit = max_depth(options.stack_size());
+    if nesting_depth(&fm.src) > limit {
+        return Err(TooDeeplyNested { limit }.into());
+    }
+
+    let program =
+        tracing::info_span!("parse").in_scope(|| get_program(c, fm.clone(), handler, options))?;
+    stats.parse_ms = timer.lap();
+    cancel.check()?;
+
+    let mut output = match program {
+        Some(program) => {
+            // Generated files are still compiled, as they may need the
+            // TypeScript and module transforms to run, but not walked.
+            let collected = if skipped.is_some() {
+                Collected::default()
+            } else {
+                let collected = tracing::info_span!("ranges")
+                    .in_scope(|| collect_ranges(&program, c.cm.clone(), limit, options.tests));
+                if collected.too_deep {
+                    return Err(TooDeeplyNested { limit }.into());
+                }
+                stats.ranges_ms = timer.lap();
+                stats.nodes = collected.nodes;
+                cancel.check()?;
+                collected
+            };
+
+            // Before code generation, which takes the comments.
+            let tests = collected
+                .tests
+                .map(|tests| TestTree::new(c.comments(), &fm, tests));
+
+            // Before any pass, which could elide type-only imports.
+            let dependencies = if options.dependencies && skipped.is_none() {
+                let dependencies = tracing::info_span!("dependencies")
+                    .in_scope(|| collect_dependencies(&program, &c.cm));
+                Some(dependencies)
+            } else {
+                None
+            };
+
+            let program = if options.hoist_mocks {
+                // Only the errors of this pass, not those of the parser.
+                let errors = handler.err_count();
+                let program = tracing::info_span!("hoistMocks")
+                    .in_scope(|| program.fold_with(&mut hoist_mocks()));
+                if handler.err_count() > errors {
+                    bail!("invalid mock factories in {}", options.swc.filename)
+                }
+                program
+            } else {
+                program
+            };
+
+            let (program, coverage) = if options.instrument && skipped.is_none() {
+                let (program, coverage) = tracing::info_span!("instrument").in_scope(|| {
+                    instrument(program, &c.cm, &options.swc.filename, &fm.src, &collected.ranges)
+                })?;
+                (program, Some(coverage))
+            } else {
+                (program, None)
+            };
+
+            let res = run_swc(c, handler, &fm, program, options, cancel, &mut timer, &mut stats)?;
+            cancel.check()?;
+
+            TransformOutputWithRanges {
+                code: res.code,
+                map: shape_map(res.map, options)?,
+                ranges: collected.ranges,
+                skipped,
+                swcrc,
+                coverage,
+                tests,
+                dependencies,
+                ..Default::default()
+            }
+        }
+        None => TransformOutputWithRanges {
+            code: fm.src.to_string(),
+            ignored: true,
+            swcrc,
+            ..Default::default()
+        },
+    };
+
+    if let Some((ref key, ref cache)) = cache {
+        // A cache which cannot be written to should not fail the build.
+        let _ = cache.put(key, &output);
+    }
+
+    finish_stats(&mut output, stats, &timer, options);
+    Ok(output)
+}
+
+/// Records `stats` in the process-wide totals, and returns them with the
+/// output if they were requested.
+fn finish_stats(
+    output: &mut TransformOutputWithRanges,
+    mut stats: TransformStats,
+    timer: &Timer,
+    options: &TransformOptions,
+) {
+    stats.total_ms = timer.total();
+    stats.ranges = output.ranges.len();
+    stats.output_size = output.code.len();
+    stats.map_size = match output.map {
+        Some(SourceMapOutput::Json(ref json)) => json.len(),
+        Some(SourceMapOutput::Object(ref map)) => map.to_string().len(),
+        None => 0,
+    };
+
+    stats::record(&stats);
+    output.stats = if options.stats { Some(stats) } else { None };
+}
+
+/// Runs the swc pass chain and code generation on `program`, like
+/// `Compiler::process_js`, but as separate phases with their own spans and
+/// timings. `cancel` is checked between the two.
+///
+/// The minifier is part of swc's pass chain, so with `minify` the mocks are
+/// hoisted in its output, like in that of the module transforms.
+#[allow(clippy::too_many_arguments)]
+fn run_swc(
+    c: &Compiler,
+    handler: &Handler,
+    fm: &SourceFile,
+    program: Program,
+    options: &TransformOptions,
+    cancel: &CancelToken,
+    timer: &mut Timer,
+    stats: &mut TransformStats,
+) -> Result<TransformOutput, Error> {
+    let swc_options = &swc_options_for(fm, options)?;
+    let config = match c.config_for_file(handler, swc_options, &fm.name)? {
+        Some(config) => config,
+        None => bail!("cannot process file because it's ignored by .swcrc"),
+    };
+    if config.minify {
+        // Like `process_js`, keep only `/*! ... */` comments.
+        let preserved = |_: &BytePos, comments: &mut Vec<Comment>| {
+            comments.retain(|comment| comment.text.starts_with('!'));
+            !comments.is_empty()
+        };
+        c.comments().leading.retain(preserved);
+        c.comments().trailing.retain(preserved);
+    }
+
+    let orig = match swc_options.config.input_source_map {
+        InputSourceMap::Str(ref json) => Some(
+            sourcemap::SourceMap::from_slice(json.as_bytes())
+                .context("invalid input source map")?,
+        ),
+        _ => None,
+    };
+
+    // The module transforms move the imports above the hoisted mocks again.
+    let mut pass = chain!(
+        config.pass,
+        Optional::new(hoist_mocks_after_modules(), options.hoist_mocks)
+    );
+    let program = tracing::info_span!("passes").in_scope(|| {
+        HELPERS.set(&Helpers::new(config.external_helpers), || {
+            HANDLER.set(handler, || program.fold_with(&mut pass))
+        })
+    });
+    stats.transform_ms = timer.lap();
+    cancel.check()?;
+
+    let output = tracing::info_span!("codegen").in_scope(|| {
+        c.print(
+            &program,
+            config.output_path,
+            config.target,
+            config.source_maps,
+            orig.as_ref(),
+            config.minify,
+            Some(c.comments()),
+        )
+    });
+    stats.codegen_ms = timer.lap();
+    output
+}
+
+/// The swc options for compiling `fm`, with the input source map resolved.
+fn swc_options_for(
+    fm: &SourceFile,
+    options: &TransformOptions,
+) -> Result<swc::config::Options, Error> {
+    let mut swc_options = options.swc.clone();
+
+    if options.source_map.sources_content == Some(true) {
+        swc_options.config.inline_sources_content = true;
+    }
+
+    if options.swc.source_maps.is_some() {
+        let input_map =
+            load_input_source_map(fm, &options.swc.filename, &options.input_source_map)?;
+        // Set it even if there is no map, so swc does not look for one itself.
+        swc_options.config.input_source_map = match input_map {
+            Some(json) => InputSourceMap::Str(json),
+            None => InputSourceMap::Bool(false),
+        };
+    }
+
+    Ok(swc_options)
+}
+
+fn shape_map(
+    map: Option<String>,
+    options: &TransformOptions,
+) -> Result<Option<SourceMapOutput>, Error> {
+    map.map(|json| shape_source_map(json, &options.source_map))
+        .transpose()
+}
+
+/// Custom passes which affect the output, with their versions.
+///
+/// Bump a version when the output of its pass changes, so that persistent
+/// caches are invalidated.
+pub const PASS_VERSIONS: &[(&str, u32)] = &[
+    ("ranges", RANGES_VERSION),
+    ("hoistMocks", HOIST_MOCKS_VERSION),
+    ("coverage", COVERAGE_VERSION),
+    ("tests", TESTS_VERSION),
+    ("dependencies", DEPENDENCIES_VERSION),
+];
+
+/// The entries of [PASS_VERSIONS] which are enabled by `options`.
+pub fn enabled_passes(options: &TransformOptions) -> Vec<(&'static str, u32)> {
+    PASS_VERSIONS
+        .iter()
+        .filter(|(name, _)| match *name {
+            "hoistMocks" => options.hoist_mocks,
+            "coverage" => options.instrument,
+            "tests" => options.tests,
+            "dependencies" => options.dependencies,
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Reads a source file from disk.
+///
+/// UTF-8 and UTF-16 (LE/BE) files are supported. The encoding is detected
+/// from the byte order mark, which is stripped. Files without a BOM are
+/// treated as UTF-8.
+pub fn load_file(c: &Compiler, path: &Path) -> Result<Arc<SourceFile>, Error> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let src = decode_source(bytes).with_context(|| format!("failed to decode {}", path.display()))?;
+
+    Ok(c.cm.new_source_file(FileName::Real(path.to_path_buf()), src))
+}
+
+impl TransformOptions {
+    pub(crate) fn stack_size(&self) -> usize {
+        self.stack_size.unwrap_or(DEFAULT_STACK_SIZE)
+    }
+
+    /// Uses `path` as `filename` unless one was given explicitly, so `.swcrc`
+    /// resolution and the source map `sources` pick it up.
+    pub fn set_default_filename(&mut self, path: &Path) {
+        if self.swc.filename.is_empty() {
+            self.swc.filename = path.to_string_lossy().into_owned();
+        }
+    }
+}
+
+/// Parses `fm`, or returns `None` if it is ignored by `.swcrc`.
 pub fn get_program(
     c: &Arc<Compiler>,
     fm: Arc<SourceFile>,
     handler: &Handler,
     options: &TransformOptions,
-) -> Result<Program, Error> {
+) -> Result<Option<Program>, Error> {
     // From `process_js_file`...
 
     let opts = &options.swc;
@@ -207,29 +689,11 @@ pub fn get_program(
     let config = c.config_for_file(handler, opts, &fm.name)?;
     let config = match config {
         Some(v) => v,
-        None => {
-            bail!("cannot process file because it's ignored by .swcrc")
-        }
-    };
-
-    let custom_before_pass = noop();
-    let custom_after_pass = noop();
-
-    let config = BuiltConfig {
-        pass: chain!(custom_before_pass, config.pass, custom_after_pass),
-        syntax: config.syntax,
-        target: config.target,
-        minify: config.minify,
-        external_helpers: config.external_helpers,
-        source_maps: config.source_maps,
-        input_source_map: config.input_source_map,
-        is_module: config.is_module,
-        output_path: config.output_path,
-        source_file_name: config.source_file_name,
-        preserve_comments: config.preserve_comments,
-        inline_sources_content: config.inline_sources_content,
+        None => return Ok(None),
     };
 
+    // Only the parser settings are used. The input source map is resolved by
+    // `process_file`, and `process_js` builds its own pass chain.
     let program = c.parse_js(
         fm.clone(),
         handler,
@@ -239,9 +703,10 @@ pub fn get_program(
         true,
     )?;
 
-    Ok(program)
+    Ok(Some(program))
 }
 
+#[cfg(feature = "node")]
 #[js_function(4)]
 pub fn transform(cx: CallContext) -> napi::Result<JsObject> {
     schedule_transform(cx, |c, src, _, options| {
@@ -258,10 +723,12 @@ pub fn transform(cx: CallContext) -> napi::Result<JsObject> {
             c: c.clone(),
             input,
             options,
+            cancel: Default::default(),
         }
     })
 }
 
+#[cfg(feature = "node")]
 #[js_function(4)]
 pub fn transform_sync(cx: CallContext) -> napi::Result<JsObject> {
     exec_transform(cx, |c, src, options| {
@@ -276,6 +743,85 @@ pub fn transform_sync(cx: CallContext) -> napi::Result<JsObject> {
     })
 }
 
+#[cfg(feature = "node")]
+#[js_function(4)]
+pub fn transform_file(cx: CallContext) -> napi::Result<JsObject> {
+    schedule_transform(cx, |c, path, _, mut options| {
+        let path = PathBuf::from(path);
+        options.set_default_filename(&path);
+
+        TransformTask {
+            c: c.clone(),
+            input: Input::File(path),
+            options,
+            cancel: Default::default(),
+        }
+    })
+}
+
+#[cfg(feature = "node")]
+#[js_function(4)]
+pub fn transform_file_sync(cx: CallContext) -> napi::Result<JsObject> {
+    let c = get_compiler(&cx);
+
+    let path = PathBuf::from(cx.get::<JsString>(0)?.into_utf8()?.as_str()?);
+    let mut options = get_options(&cx, 2)?;
+    options.set_default_filename(&path);
+
+    let output = compile_file(&c, &path, &options).convert_err()?;
+
+    complete_output_with_ranges(cx.env, output)
+}
+
+/// Reads and compiles the file at `path` on the calling thread, like
+/// `transformFileSync`.
+///
+/// Panics and too deeply nested input are returned as errors.
+pub fn compile_file(
+    c: &Arc<Compiler>,
+    path: &Path,
+    options: &TransformOptions,
+) -> Result<TransformOutputWithRanges, Error> {
+    let cancel = CancelToken::new(None, options.timeout_ms);
+
+    run_with_stack(options.stack_size(), || {
+        catch_panic(&options.swc.filename, || {
+            try_with_handler(c.cm.clone(), |handler| {
+                c.run(|| {
+                    let fm = load_file(c, path)?;
+                    cancel.check()?;
+                    process_file(c, fm, handler, options, &cancel)
+                })
+            })
+        })
+    })
+}
+
+/// Compiles `src` on the calling thread, like `transformSync` with a string.
+pub fn compile_source(
+    c: &Arc<Compiler>,
+    src: String,
+    options: &TransformOptions,
+) -> Result<TransformOutputWithRanges, Error> {
+    let cancel = CancelToken::new(None, options.timeout_ms);
+    let name = if options.swc.filename.is_empty() {
+        FileName::Anon
+    } else {
+        FileName::Real(options.swc.filename.clone().into())
+    };
+
+    run_with_stack(options.stack_size(), || {
+        catch_panic(&options.swc.filename, || {
+            try_with_handler(c.cm.clone(), |handler| {
+                c.run(|| {
+                    let fm = c.cm.new_source_file(name, src);
+                    process_file(c, fm, handler, options, &cancel)
+                })
+            })
+        })
+    })
+}
+
 #[test]
 fn test_deser() {
     const JSON_STR: &str = r#"{"jsc":{"parser":{"syntax":"ecmascript","dynamicImport":true,"jsx":true},"transform":{"react":{"runtime":"automatic","pragma":"React.createElement","pragmaFrag":"React.Fragment","throwIfNamespace":true,"development":false,"useBuiltins":true}},"target":"es5"},"filename":"/Users/timneutkens/projects/next.js/packages/next/dist/client/next.js","sourceMaps":false,"sourceFileName":"/Users/timneutkens/projects/next.js/packages/next/dist/client/next.js"}"#;
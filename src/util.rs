@@ -26,20 +26,34 @@ IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 DEALINGS IN THE SOFTWARE.
 */
 
-use anyhow::Context;
-use napi::{CallContext, JsBuffer, Status};
-use serde::de::DeserializeOwned;
-use std::any::type_name;
+use anyhow::{bail, Error};
+#[cfg(feature = "node")]
+use {
+    crate::cancel::Cancelled,
+    anyhow::Context,
+    napi::{CallContext, JsBuffer, Status},
+    serde::de::DeserializeOwned,
+    std::any::type_name,
+};
 
+#[cfg(feature = "node")]
 pub trait MapErr<T>: Into<Result<T, anyhow::Error>> {
     fn convert_err(self) -> napi::Result<T> {
-        self.into()
-            .map_err(|err| napi::Error::new(Status::GenericFailure, format!("{:?}", err)))
+        self.into().map_err(|err| {
+            let status = if err.chain().any(|cause| cause.is::<Cancelled>()) {
+                Status::Cancelled
+            } else {
+                Status::GenericFailure
+            };
+            napi::Error::new(status, format!("{:?}", err))
+        })
     }
 }
 
+#[cfg(feature = "node")]
 impl<T> MapErr<T> for Result<T, anyhow::Error> {}
 
+#[cfg(feature = "node")]
 pub trait CtxtExt {
     /// Currently this uses JsBuffer
     fn get_deserialized<T>(&self, index: usize) -> napi::Result<T>
@@ -47,6 +61,7 @@ pub trait CtxtExt {
         T: DeserializeOwned;
 }
 
+#[cfg(feature = "node")]
 impl CtxtExt for CallContext<'_> {
     fn get_deserialized<T>(&self, index: usize) -> napi::Result<T>
     where
@@ -67,3 +82,40 @@ impl CtxtExt for CallContext<'_> {
         Ok(v)
     }
 }
+
+/// Decodes the raw bytes of a source file into a string.
+///
+/// A UTF-8 byte order mark is stripped. UTF-16 is only recognized by its
+/// byte order mark.
+pub fn decode_source(mut bytes: Vec<u8>) -> Result<String, Error> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        bytes.drain(..3);
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        return decode_utf16(&bytes[2..], u16::from_le_bytes);
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        return decode_utf16(&bytes[2..], u16::from_be_bytes);
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Result<String, Error> {
+    if bytes.len() % 2 != 0 {
+        bail!("UTF-16 source has an odd number of bytes")
+    }
+
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| to_unit([unit[0], unit[1]]))
+        .collect();
+
+    Ok(String::from_utf16(&units)?)
+}
+
+#[test]
+fn test_decode_source() {
+    assert_eq!(decode_source(b"\xEF\xBB\xBFvar a;".to_vec()).unwrap(), "var a;");
+    assert_eq!(decode_source(b"\xFF\xFEa\0;\0".to_vec()).unwrap(), "a;");
+    assert_eq!(decode_source(b"\xFE\xFF\0a\0;".to_vec()).unwrap(), "a;");
+    assert!(decode_source(b"\xFF\xFEa".to_vec()).is_err());
+}
@@ -0,0 +1,119 @@
+//! WebAssembly bindings, for browsers and sandboxes which cannot load native
+//! addons. Build them with `npm run build-wasm`.
+//!
+//! The exports take the options of their N-API counterparts and return the
+//! same output, but only compile source strings. There is no file system, so
+//! `.swcrc` files are not found and `cacheDir` must not be set. Errors are
+//! thrown as strings.
+//!
+//! `wasm32-unknown-unknown` aborts on panics, so unlike in the addon a panic
+//! is not turned into an error: it traps the instance, which must not be
+//! used afterwards. The panic message is logged with `console.error` first.
+
+use crate::{
+    api::options_from_json,
+    compiler,
+    stack::{run_with_stack, DEFAULT_STACK_SIZE},
+    transform::compile_source,
+};
+use anyhow::{Context as _, Error};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fmt::Debug, panic};
+use swc::{
+    config::{JsMinifyOptions, ParseOptions},
+    try_with_handler,
+};
+use swc_common::FileName;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(message: &str);
+}
+
+/// Logs panics, as they abort without unwinding.
+#[wasm_bindgen(start)]
+pub fn start() {
+    panic::set_hook(Box::new(|panic_info| console_error(&panic_info.to_string())));
+}
+
+/// `transformSync(src, options)`, returning `{code, map, ranges, ...}`.
+#[wasm_bindgen(js_name = "transformSync")]
+pub fn transform_sync(src: String, options: JsValue) -> Result<JsValue, JsValue> {
+    let options = deserialize::<Value>(&options)?;
+    let options = options_from_json(&options.to_string()).map_err(to_js_error)?;
+
+    let output = compile_source(&compiler(), src, &options).map_err(to_js_error)?;
+    JsValue::from_serde(&output).map_err(to_js_error)
+}
+
+/// `minifySync(src, options)`, returning `{code, map}`.
+#[wasm_bindgen(js_name = "minifySync")]
+pub fn minify_sync(src: String, options: JsValue) -> Result<JsValue, JsValue> {
+    let options = deserialize::<JsMinifyOptions>(&options)?;
+    let c = compiler();
+
+    let output = run_with_stack(DEFAULT_STACK_SIZE, || {
+        try_with_handler(c.cm.clone(), |handler| {
+            let fm = c.cm.new_source_file(FileName::Anon, src);
+            c.minify(fm, handler, &options)
+        })
+    })
+    .map_err(to_js_error)?;
+    JsValue::from_serde(&output).map_err(to_js_error)
+}
+
+/// `parseSync(src, options, filename?)`, returning the AST.
+#[wasm_bindgen(js_name = "parseSync")]
+pub fn parse_sync(
+    src: String,
+    options: JsValue,
+    filename: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let options = deserialize::<ParseOptions>(&options)?;
+    let filename = filename.unwrap_or_default();
+    let name = if filename.is_empty() {
+        FileName::Anon
+    } else {
+        FileName::Real(filename.into())
+    };
+    let c = compiler();
+
+    let program = run_with_stack(DEFAULT_STACK_SIZE, || {
+        try_with_handler(c.cm.clone(), |handler| {
+            c.run(|| {
+                let fm = c.cm.new_source_file(name, src);
+                c.parse_js(
+                    fm,
+                    handler,
+                    options.target,
+                    options.syntax,
+                    options.is_module,
+                    options.comments,
+                )
+            })
+        })
+    })
+    .map_err(to_js_error)?;
+    JsValue::from_serde(&program).map_err(to_js_error)
+}
+
+/// Deserializes options, treating `undefined` and `null` as `{}`.
+fn deserialize<T>(value: &JsValue) -> Result<T, JsValue>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let result: Result<T, Error> = if value.is_undefined() || value.is_null() {
+        serde_json::from_str("{}").map_err(Error::from)
+    } else {
+        value.into_serde().map_err(Error::from)
+    };
+
+    result.context("invalid options").map_err(to_js_error)
+}
+
+fn to_js_error(err: impl Debug) -> JsValue {
+    JsValue::from_str(&format!("{:?}", err))
+}
@@ -3,12 +3,13 @@ use self::next_dynamic::next_dynamic;
 use self::next_ssg::next_ssg;
 use self::styled_jsx::styled_jsx;
 use std::path::PathBuf;
-use swc_common::{chain, comments::SingleThreadedComments, FileName};
+use swc_common::{chain, comments::SingleThreadedComments, FileName, Mark};
 use swc_ecma_transforms_testing::{test, test_fixture};
 use swc_ecmascript::{
     parser::{EsConfig, Syntax},
-    transforms::react::jsx,
+    transforms::{modules::common_js, react::jsx},
 };
+use swc_wallaby::hoist_mocks::{hoist_mocks, hoist_mocks_after_modules};
 use testing::fixture;
 
 #[path = "../src/amp_attributes.rs"]
@@ -34,6 +35,33 @@ fn amp_attributes_fixture(input: PathBuf) {
     test_fixture(syntax(), &|_tr| amp_attributes(), &input, &output);
 }
 
+#[fixture("tests/fixture/hoist-mocks/**/input.js")]
+fn hoist_mocks_fixture(input: PathBuf) {
+    let output = input.parent().unwrap().join("output.js");
+    test_fixture(syntax(), &|_tr| hoist_mocks(), &input, &output);
+}
+
+#[fixture("tests/fixture/hoist-mocks-commonjs/**/input.js")]
+fn hoist_mocks_commonjs_fixture(input: PathBuf) {
+    let output = input.parent().unwrap().join("output.js");
+    test_fixture(
+        syntax(),
+        &|_tr| {
+            let config = swc_ecmascript::transforms::modules::util::Config {
+                strict_mode: true,
+                ..Default::default()
+            };
+            chain!(
+                hoist_mocks(),
+                common_js(Mark::fresh(Mark::root()), config, None),
+                hoist_mocks_after_modules()
+            )
+        },
+        &input,
+        &output,
+    );
+}
+
 #[fixture("tests/fixture/next-dynamic/**/input.js")]
 fn next_dynamic_fixture(input: PathBuf) {
     let output = input.parent().unwrap().join("output.js");
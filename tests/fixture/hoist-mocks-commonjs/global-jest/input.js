@@ -0,0 +1,4 @@
+import { a } from './a'
+
+console.log(a)
+jest.mock('./a')
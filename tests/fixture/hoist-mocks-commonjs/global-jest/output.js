@@ -0,0 +1,4 @@
+"use strict";
+jest.mock("./a");
+var _a = require("./a");
+console.log(_a.a);
@@ -0,0 +1,7 @@
+import { jest } from '@jest/globals'
+import { a } from './a'
+
+jest.mock('./a', () => ({ a: mockA }))
+
+const mockA = 1
+console.log(a)
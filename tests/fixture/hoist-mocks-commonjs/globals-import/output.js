@@ -0,0 +1,9 @@
+"use strict";
+var _globals = require("@jest/globals");
+_globals.jest.mock("./a", ()=>({
+        a: mockA
+    })
+);
+var _a = require("./a");
+const mockA = 1;
+console.log(_a.a);
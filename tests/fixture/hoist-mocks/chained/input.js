@@ -0,0 +1,7 @@
+import { a } from './a'
+
+console.log(a)
+
+jest.mock('./a').mock('./b', () => ({ b: 1 }))
+jest.unmock('./c')
+jest.spyOn(console, 'log')
@@ -0,0 +1,8 @@
+jest.mock("./a").mock("./b", ()=>({
+        b: 1
+    })
+);
+jest.unmock("./c");
+import { a } from "./a";
+console.log(a);
+jest.spyOn(console, "log");
@@ -0,0 +1,11 @@
+describe('a', () => {
+  const value = 1
+  jest.mock('./a')
+
+  it('works', () => {
+    if (value) {
+      console.log(value)
+      jest.unmock('./b')
+    }
+  })
+})
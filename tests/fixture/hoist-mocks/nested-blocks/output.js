@@ -0,0 +1,10 @@
+describe("a", ()=>{
+    jest.mock("./a");
+    const value = 1;
+    it("works", ()=>{
+        if (value) {
+            jest.unmock("./b");
+            console.log(value);
+        }
+    });
+});
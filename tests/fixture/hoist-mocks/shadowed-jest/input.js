@@ -0,0 +1,4 @@
+import { a } from './a'
+
+const jest = { mock() {} }
+jest.mock('./a', () => ({ a }))
@@ -0,0 +1,9 @@
+import { a } from "./a";
+const jest = {
+    mock () {
+    }
+};
+jest.mock("./a", ()=>({
+        a
+    })
+);
@@ -0,0 +1,8 @@
+'use strict'
+import { a } from './a'
+import { vi } from 'vitest'
+
+const mocks = vi.hoisted(() => ({ a: vi.fn() }))
+vi.mock('./a', () => ({ a: mocks.a }))
+
+console.log(a)
@@ -0,0 +1,12 @@
+"use strict";
+import { vi } from "vitest";
+const mocks = vi.hoisted(()=>({
+        a: vi.fn()
+    })
+);
+vi.mock("./a", ()=>({
+        a: mocks.a
+    })
+);
+import { a } from "./a";
+console.log(a);
@@ -29,6 +29,7 @@ fn ranges() {
                 // TODO(vjpr): Next.js stuff. Remove.
                 disable_next_ssg: false,
                 pages_dir: None,
+                ..Default::default()
             },
         )
     };
@@ -0,0 +1,23 @@
+// Exercises the tracing callback of the native build. Run
+// `npm run build-native` first.
+import assert from 'assert'
+import {initTracing, transformSync} from '../index.js'
+
+const events = []
+initTracing({callback: (event) => events.push(event)})
+
+transformSync('const a = 1;\n', {filename: 'a.js', swcrc: false})
+
+// Events are queued to the JS thread, and the callback does not keep the
+// process alive.
+await new Promise((resolve) => setTimeout(resolve, 100))
+
+assert(events.length > 0, 'expected trace events')
+for (const event of events) {
+  assert.strictEqual(typeof event, 'object', JSON.stringify(events))
+  assert.strictEqual(typeof event.name, 'string')
+  assert(event.ph === 'X' || event.ph === 'i', event.ph)
+}
+assert(events.some((event) => event.name === 'transform'), JSON.stringify(events))
+
+console.log('tracing: ok')
@@ -0,0 +1,192 @@
+//! End-to-end checks of the options which add to the output of a transform,
+//! through `compile_source` like the JS API.
+
+use anyhow::Error;
+use serde_json::{json, Value};
+use swc_wallaby::{
+    compiler,
+    dependencies::DependencyKind,
+    generated::SkipReason,
+    test_discovery::{TestKind, TestModifier},
+    transform::{compile_source, TransformOptions, TransformOutputWithRanges},
+};
+
+/// Compiles `src` with `options`, without reading `.swcrc` files.
+fn compile(src: &str, mut options: Value) -> Result<TransformOutputWithRanges, Error> {
+    options["swcrc"] = false.into();
+    let options: TransformOptions = serde_json::from_value(options)?;
+    compile_source(&compiler(), src.into(), &options)
+}
+
+/// The index of the first line of `code` which contains `needle`.
+fn line(code: &str, needle: &str) -> usize {
+    code.lines()
+        .position(|l| l.contains(needle))
+        .unwrap_or_else(|| panic!("no {} in\n{}", needle, code))
+}
+
+#[test]
+fn stats() {
+    let output = compile("a?.b;", json!({"filename": "a.js", "stats": true})).unwrap();
+
+    let stats = output.stats.unwrap();
+    assert!(stats.transform_ms > 0.0 && stats.codegen_ms > 0.0);
+    assert!(stats.total_ms >= stats.parse_ms + stats.transform_ms + stats.codegen_ms);
+}
+
+#[test]
+fn skipped_generated_files_are_compiled() {
+    let options = json!({
+        "filename": "a.ts",
+        "skipGenerated": true,
+        "jsc": {"parser": {"syntax": "typescript"}},
+        "module": {"type": "commonjs"}
+    });
+    let src = "// @generated\nimport {b} from './b';\nexport const a: number = b;\n";
+    let output = compile(src, options).unwrap();
+
+    assert_eq!(output.skipped, Some(SkipReason::GeneratedHeader));
+    assert!(output.ranges.is_empty());
+    assert!(output.code.contains("require(\"./b\")"));
+    assert!(!output.code.contains(": number"));
+}
+
+#[test]
+fn instrument() {
+    let src = "'use strict';\nconst a = 1;\nfunction f() {\n    return a;\n}\nf();\n";
+    let output = compile(src, json!({"filename": "a.js", "instrument": true})).unwrap();
+
+    let coverage = output.coverage.unwrap();
+    assert_eq!(coverage.statement_map.len(), 3);
+    assert_eq!(coverage.fn_map[&0].name, "f");
+    let header = output.code.find("var __swc_wallaby_cov").unwrap();
+    assert!(output.code.find("use strict").unwrap() < header);
+    assert!(output.code.contains("__swc_wallaby_cov.f[0]++"));
+}
+
+#[test]
+fn hoist_mocks() {
+    let compile = |src: &str| compile(src, json!({"filename": "a.test.js", "hoistMocks": true}));
+
+    let output = compile(
+        "import {jest} from '@jest/globals';\nimport {a} from './a';\nconst mockA = \
+         1;\njest.mock('./a', () => ({a: mockA}));\n",
+    )
+    .unwrap();
+    let code = &output.code;
+    assert!(line(code, "@jest/globals") < line(code, "jest.mock"));
+    assert!(line(code, "jest.mock") < line(code, "import { a }"));
+    assert!(line(code, "jest.mock") < line(code, "mockA = 1"));
+
+    let err = compile("import {a} from './a';\njest.mock('./b', () => ({b: a}));\n").unwrap_err();
+    assert!(format!("{:?}", err).contains("Invalid variable access: a"));
+
+    // Only top-level bindings shadow globals.
+    compile(
+        "function f(process) {}\njest.mock('./a', () => ({env: process.env, b: undeclared}));\n",
+    )
+    .unwrap();
+
+    let err = compile("const factory = () => ({});\njest.mock('./a', factory);\n").unwrap_err();
+    assert!(format!("{:?}", err).contains("of `jest.mock()` must be an inline function"));
+}
+
+#[test]
+fn hoist_mocks_commonjs() {
+    let options = json!({
+        "filename": "a.test.js",
+        "hoistMocks": true,
+        "module": {"type": "commonjs"}
+    });
+    let src = "import {vi} from 'vitest';\nimport {a} from './a';\nvi.mock('./a');\n";
+    let code = compile(src, options).unwrap().code;
+
+    assert!(line(&code, "require(\"vitest\")") < line(&code, "vi.mock"));
+    assert!(line(&code, "vi.mock") < line(&code, "require(\"./a\")"));
+}
+
+#[test]
+fn hoist_mocks_minified() {
+    let options = json!({
+        "filename": "a.test.js",
+        "hoistMocks": true,
+        "minify": true,
+        "module": {"type": "commonjs"}
+    });
+    let src =
+        "import {vi} from 'vitest';\nimport {a} from './a';\nvi.mock('./a');\nconsole.log(a);\n";
+    let code = compile(src, options).unwrap().code;
+
+    let pos = |needle: &str| code.find(needle).unwrap();
+    assert!(pos("require(\"vitest\")") < pos("vi.mock("), "{}", code);
+    assert!(pos("vi.mock(") < pos("require(\"./a\")"), "{}", code);
+}
+
+#[test]
+fn tests() {
+    let src = "//file.only
+describe('math', () => {
+    it('adds', () => {});
+    it.skip(`subtracts`, () => {});
+    test.each([[1, 2]])(`adds ${1}`, () => {});
+    xit(name, () => {});
+});
+it.todo('later');
+const s = `
+//file.skip
+`;
+";
+    let output = compile(src, json!({"filename": "a.test.js", "tests": true})).unwrap();
+
+    let tree = output.tests.unwrap();
+    assert!(tree.only && !tree.skip);
+    assert_eq!(tree.tests.len(), 2);
+    let suite = &tree.tests[0];
+    assert_eq!(suite.kind, TestKind::Suite);
+    assert_eq!(suite.range, vec![2, 0, 7, 2]);
+    let names: Vec<_> = suite.children.iter().map(|t| t.name.as_deref()).collect();
+    assert_eq!(names, vec![Some("adds"), Some("subtracts"), Some("`adds ${1}`"), None]);
+    assert_eq!(suite.children[1].modifier, Some(TestModifier::Skip));
+    assert!(suite.children[2].each && suite.children[2].template);
+    assert_eq!(suite.children[3].modifier, Some(TestModifier::Skip));
+    assert_eq!(tree.tests[1].modifier, Some(TestModifier::Todo));
+}
+
+#[test]
+fn dependencies() {
+    let options = json!({
+        "filename": "a.ts",
+        "dependencies": true,
+        "jsc": {"parser": {"syntax": "typescript", "dynamicImport": true}}
+    });
+    let src = "import {T} from './types';
+import type {U} from './more-types';
+export * from './all';
+const a = require('./a'), b = require(name);
+const c = import(`./c`);
+const d = import.meta.glob(['./d/*.ts', './e/*.ts']);
+let t: T & U;
+";
+    let output = compile(src, options).unwrap();
+
+    // `./types` is elided from the output, but still a dependency.
+    assert!(!output.code.contains("./types"));
+    let dependencies: Vec<_> = output
+        .dependencies
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.specifier, d.kind, d.type_only))
+        .collect();
+    assert_eq!(
+        dependencies,
+        vec![
+            ("./types".to_string(), DependencyKind::Import, false),
+            ("./more-types".to_string(), DependencyKind::Import, true),
+            ("./all".to_string(), DependencyKind::Export, false),
+            ("./a".to_string(), DependencyKind::Require, false),
+            ("./c".to_string(), DependencyKind::DynamicImport, false),
+            ("./d/*.ts".to_string(), DependencyKind::Glob, false),
+            ("./e/*.ts".to_string(), DependencyKind::Glob, false),
+        ]
+    );
+}
@@ -0,0 +1,33 @@
+// Exercises the wasm build. Run `npm run build-wasm` first.
+import assert from 'assert'
+import {createRequire} from 'module'
+
+const require = createRequire(import.meta.url)
+const {transformSync, minifySync, parseSync} = require('../wasm/swc_wallaby.js')
+
+const src = 'const a = {b: 1};\nexport const c = a?.b ?? 2;\n'
+
+const output = transformSync(src, {
+  filename: 'a.js',
+  swcrc: false,
+  sourceMaps: true,
+  jsc: {target: 'es2015'},
+})
+assert.strictEqual(typeof output.code, 'string')
+assert(!output.code.includes('?.'), output.code)
+assert(output.map, 'expected a source map')
+assert(Array.isArray(output.ranges) && output.ranges.length > 0)
+for (const range of output.ranges) {
+  assert.strictEqual(range.length, 4)
+}
+
+assert.throws(() => transformSync('const = ;', {filename: 'b.js', swcrc: false}))
+
+const minified = minifySync('function f(a) { return a + 1 }\nf(2)', {compress: true, mangle: true})
+assert(minified.code.length > 0 && minified.code.length < 30, minified.code)
+
+const ast = parseSync('let x: number = 1', {syntax: 'typescript'}, 'c.ts')
+assert.strictEqual(ast.type, 'Module')
+assert.strictEqual(ast.body[0].type, 'VariableDeclaration')
+
+console.log('wasm: ok')